@@ -8,6 +8,7 @@ fn main() -> Result<()> {
     builder
         .out_dir("src/pb")
         .with_type_attributes(&["MaterializeRequest"], &[r#"#[derive(Eq, Hash)]"#])
+        .with_serde(&["LocalizedTemplate"], true, true, None)
         .compile(
             &[
                 "../protos/metadata/messages.proto",
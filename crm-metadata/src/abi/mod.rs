@@ -1,8 +1,10 @@
-use std::collections::HashSet;
+mod template;
+
+use std::{collections::HashSet, slice};
 
 use crate::{
     pb::{Content, MaterializeRequest, Publisher},
-    MetadataService, ResponseStream, ServiceResult,
+    MetadataService, ResponseStream, ServiceResult, Tpl,
 };
 use chrono::{DateTime, Days, Utc};
 use fake::{
@@ -56,16 +58,9 @@ impl Content {
         }
     }
 
+    /// plain text card of the content
     pub fn to_body(&self) -> String {
-        format!("Content: {:?}", self)
-    }
-}
-
-pub struct Tpl<'a>(pub &'a [Content]);
-
-impl<'a> Tpl<'a> {
-    pub fn to_body(&self) -> String {
-        format!("Tpl: {:?}", self.0)
+        Tpl::new(slice::from_ref(self)).to_text(&self.name)
     }
 }
 
@@ -106,8 +101,9 @@ mod tests {
 
     #[tokio::test]
     async fn materialize_should_work() -> Result<()> {
-        let config = AppConfig::load()?;
-        let service = MetadataService::new(config);
+        let mut config = AppConfig::load()?;
+        config.templates.dir = std::env::temp_dir().join("crm-metadata-test-templates");
+        let service = MetadataService::new(config)?;
         let stream = tokio_stream::iter(vec![
             Ok(MaterializeRequest { id: 1 }),
             Ok(MaterializeRequest { id: 2 }),
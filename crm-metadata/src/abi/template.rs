@@ -0,0 +1,34 @@
+use crate::{
+    pb::{
+        CreateTemplateRequest, GetTemplateRequest, ListTemplatesRequest, ListTemplatesResponse,
+        PublishVersionRequest, Template,
+    },
+    MetadataService, ServiceResult,
+};
+use tonic::Response;
+
+impl MetadataService {
+    pub async fn create_template(&self, req: CreateTemplateRequest) -> ServiceResult<Template> {
+        self.templates.create(req).map(Response::new)
+    }
+
+    pub async fn get_template(&self, req: GetTemplateRequest) -> ServiceResult<Template> {
+        self.templates
+            .get(&req.name, req.version)
+            .map(Response::new)
+    }
+
+    pub async fn list_templates(
+        &self,
+        req: ListTemplatesRequest,
+    ) -> ServiceResult<ListTemplatesResponse> {
+        let templates = self.templates.list(&req.name)?;
+        Ok(Response::new(ListTemplatesResponse { templates }))
+    }
+
+    pub async fn publish_version(&self, req: PublishVersionRequest) -> ServiceResult<Template> {
+        self.templates
+            .publish(&req.name, req.version)
+            .map(Response::new)
+    }
+}
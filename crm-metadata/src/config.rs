@@ -1,11 +1,29 @@
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
-use std::{env, fs::File};
+use std::{env, fs::File, path::PathBuf};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppConfig {
     pub server: ServerConfig,
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub templates: TemplateConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemplateConfig {
+    /// directory the versioned templates are stored in, one file per template. Relative to the
+    /// working directory, deployments set an absolute path, e.g. /var/lib/crm-metadata/templates
+    pub dir: PathBuf,
+}
+
+impl Default for TemplateConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("data/templates"),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -2,21 +2,25 @@ pub mod pb;
 
 mod abi;
 mod config;
+mod tpl;
 
+use anyhow::Context;
 use std::pin::Pin;
 
-pub use abi::Tpl;
 pub use config::AppConfig;
 use futures::Stream;
 use pb::{
     metadata_server::{Metadata, MetadataServer},
-    Content, MaterializeRequest,
+    Content, CreateTemplateRequest, GetTemplateRequest, ListTemplatesRequest,
+    ListTemplatesResponse, MaterializeRequest, PublishVersionRequest, Template,
 };
 use tonic::{async_trait, Request, Response, Status, Streaming};
+pub use tpl::{Recipient, TemplateStore, Tpl};
 
 #[allow(unused)]
 pub struct MetadataService {
     config: AppConfig,
+    templates: TemplateStore,
 }
 
 type ServiceResult<T> = Result<Response<T>, Status>;
@@ -33,11 +37,42 @@ impl Metadata for MetadataService {
         let query = request.into_inner();
         self.materialize(query).await
     }
+
+    async fn create_template(
+        &self,
+        request: Request<CreateTemplateRequest>,
+    ) -> ServiceResult<Template> {
+        self.create_template(request.into_inner()).await
+    }
+
+    async fn get_template(&self, request: Request<GetTemplateRequest>) -> ServiceResult<Template> {
+        self.get_template(request.into_inner()).await
+    }
+
+    async fn list_templates(
+        &self,
+        request: Request<ListTemplatesRequest>,
+    ) -> ServiceResult<ListTemplatesResponse> {
+        self.list_templates(request.into_inner()).await
+    }
+
+    async fn publish_version(
+        &self,
+        request: Request<PublishVersionRequest>,
+    ) -> ServiceResult<Template> {
+        self.publish_version(request.into_inner()).await
+    }
 }
 
 impl MetadataService {
-    pub fn new(config: AppConfig) -> Self {
-        MetadataService { config }
+    pub fn new(config: AppConfig) -> anyhow::Result<Self> {
+        let templates = TemplateStore::load(&config.templates.dir).with_context(|| {
+            format!(
+                "Failed to load templates from {}",
+                config.templates.dir.display()
+            )
+        })?;
+        Ok(MetadataService { config, templates })
     }
 
     pub fn into_server(self) -> MetadataServer<Self> {
@@ -13,7 +13,7 @@ async fn main() -> Result<()> {
     let addr = config.server.port;
     let addr = format!("[::1]:{}", addr).parse().unwrap();
     info!("Metadata service listening on {}", addr);
-    let svc = MetadataService::new(config).into_server();
+    let svc = MetadataService::new(config)?.into_server();
     Server::builder().add_service(svc).serve(addr).await?;
     Ok(())
 }
@@ -0,0 +1,462 @@
+mod store;
+
+pub use store::TemplateStore;
+
+use crate::pb::{Content, CreateTemplateRequest, LocalizedTemplate, Template};
+use minijinja::{context, Environment};
+use serde::Serialize;
+use std::sync::OnceLock;
+use tracing::warn;
+
+/// templates are embedded in the binary, see `templates/`; localized variants are prefixed
+/// with their locale, the unprefixed ones are in the default locale
+const TEMPLATES: &[(&str, &str)] = &[
+    ("email.html", include_str!("../../templates/email.html.j2")),
+    ("email.txt", include_str!("../../templates/email.txt.j2")),
+    ("sms.txt", include_str!("../../templates/sms.txt.j2")),
+    ("in_app.txt", include_str!("../../templates/in_app.txt.j2")),
+    (
+        "zh/email.html",
+        include_str!("../../templates/zh/email.html.j2"),
+    ),
+    (
+        "zh/email.txt",
+        include_str!("../../templates/zh/email.txt.j2"),
+    ),
+    ("zh/sms.txt", include_str!("../../templates/zh/sms.txt.j2")),
+    (
+        "zh/in_app.txt",
+        include_str!("../../templates/zh/in_app.txt.j2"),
+    ),
+];
+
+/// translations of the default subjects used by the campaigns, by locale
+const SUBJECTS: &[(&str, &str, &str)] = &[
+    ("zh", "Welcome", "欢迎"),
+    ("zh", "Recall", "好久不见"),
+    ("zh", "Remind", "别忘了看完这些内容"),
+];
+
+/// locale every fallback chain ends with
+const DEFAULT_LOCALE: &str = "en";
+
+/// renders contents into the message bodies of each channel, with the builtin templates or
+/// the ones of a stored template
+pub struct Tpl<'a> {
+    contents: &'a [Content],
+    template: Option<&'a Template>,
+    locale: &'a str,
+    recipient: Recipient<'a>,
+}
+
+/// the user a body is rendered for, exposed to templates as `user` and `unfinished`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Recipient<'a> {
+    pub name: &'a str,
+    /// female, male or unknown
+    pub gender: &'a str,
+    /// contents the user started but not finished
+    pub unfinished: &'a [Content],
+}
+
+/// a body of a template
+#[derive(Debug, Clone, Copy)]
+enum Body {
+    Subject,
+    Html,
+    Text,
+    Sms,
+    InApp,
+}
+
+/// the fields of a content shown in a card
+#[derive(Debug, Serialize)]
+struct Card<'a> {
+    name: &'a str,
+    description: &'a str,
+    image: &'a str,
+    url: &'a str,
+    publishers: Vec<&'a str>,
+}
+
+impl<'a> Tpl<'a> {
+    pub fn new(contents: &'a [Content]) -> Self {
+        Self {
+            contents,
+            template: None,
+            locale: DEFAULT_LOCALE,
+            recipient: Recipient::default(),
+        }
+    }
+
+    /// render with the given template, channels it has no body for use the builtin ones
+    pub fn with_template(mut self, template: Option<&'a Template>) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// render the variants of the template for the locale, e.g. zh-CN, see [`locale_fallbacks`]
+    pub fn with_locale(mut self, locale: &'a str) -> Self {
+        if !locale.is_empty() {
+            self.locale = locale;
+        }
+        self
+    }
+
+    /// render the bodies for the recipient, the bodies are the same for every recipient unless the
+    /// template uses `user` or `unfinished`
+    pub fn with_recipient(mut self, recipient: Recipient<'a>) -> Self {
+        self.recipient = recipient;
+        self
+    }
+
+    /// subject of the template, or `default` translated to the locale if it has none or it
+    /// fails to render
+    pub fn subject(&self, default: &str) -> String {
+        self.source(Body::Subject)
+            .and_then(|subject| self.render("subject.txt", subject, default))
+            .unwrap_or_else(|| self.builtin_subject(default))
+    }
+
+    /// html email body
+    pub fn to_html(&self, subject: &str) -> String {
+        self.render_body("email.html", Body::Html, subject)
+    }
+
+    /// plain text email body
+    pub fn to_text(&self, subject: &str) -> String {
+        self.render_body("email.txt", Body::Text, subject)
+    }
+
+    /// short sms body, only the first content is shown by the builtin template
+    pub fn to_sms(&self) -> String {
+        self.render_body("sms.txt", Body::Sms, "")
+    }
+
+    /// short in-app body listing the content names
+    pub fn to_in_app(&self) -> String {
+        self.render_body("in_app.txt", Body::InApp, "")
+    }
+
+    /// a stored body failing to render falls back to the builtin one, so that the message
+    /// isn't sent empty
+    fn render_body(&self, name: &str, body: Body, subject: &str) -> String {
+        self.source(body)
+            .and_then(|source| self.render(name, source, subject))
+            .or_else(|| self.render_builtin(name, subject))
+            .unwrap_or_default()
+    }
+
+    /// first non empty source of the body along the locale fallback chain, then the default one
+    fn source(&self, body: Body) -> Option<&'a str> {
+        let template = self.template?;
+        locale_fallbacks(self.locale)
+            .filter_map(|locale| template.locales.get(locale).map(|t| body.of(t)))
+            .chain([body.of_default(template)])
+            .find(|source| !source.is_empty())
+    }
+
+    fn render(&self, name: &str, source: &str, subject: &str) -> Option<String> {
+        let ctx = self.context(subject);
+        finish(name, env().render_named_str(name, source, ctx))
+    }
+
+    /// the builtin template of the first locale along the fallback chain which has one
+    fn render_builtin(&self, name: &str, subject: &str) -> Option<String> {
+        let ctx = self.context(subject);
+        let tpl = locale_fallbacks(self.locale).find_map(|locale| match locale {
+            DEFAULT_LOCALE => env().get_template(name).ok(),
+            _ => env().get_template(&format!("{}/{}", locale, name)).ok(),
+        })?;
+        finish(name, tpl.render(ctx))
+    }
+
+    fn builtin_subject(&self, default: &str) -> String {
+        locale_fallbacks(self.locale)
+            .find_map(|locale| {
+                SUBJECTS
+                    .iter()
+                    .find(|(l, subject, _)| *l == locale && *subject == default)
+                    .map(|(_, _, translated)| translated.to_string())
+            })
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    fn context(&self, subject: &str) -> minijinja::Value {
+        let cards: Vec<_> = self.contents.iter().map(Card::from).collect();
+        let Recipient {
+            name,
+            gender,
+            unfinished,
+        } = self.recipient;
+        let unfinished: Vec<_> = unfinished.iter().map(Card::from).collect();
+        context! { subject, cards, user => context! { name, gender }, unfinished }
+    }
+}
+
+/// the locale followed by its parents and the default locale, e.g. zh-Hant-TW -> zh-Hant -> zh
+/// -> en
+pub fn locale_fallbacks(locale: &str) -> impl Iterator<Item = &str> {
+    let parents = std::iter::successors(Some(locale), |locale| {
+        locale.rfind('-').map(|pos| &locale[..pos])
+    })
+    .filter(|locale| !locale.is_empty() && *locale != DEFAULT_LOCALE);
+    parents.chain([DEFAULT_LOCALE])
+}
+
+/// make sure every body of the template compiles
+pub fn validate(req: &CreateTemplateRequest) -> Result<(), minijinja::Error> {
+    let sources = [
+        ("subject.txt", &req.subject),
+        ("email.html", &req.html),
+        ("email.txt", &req.text),
+        ("sms.txt", &req.sms),
+        ("in_app.txt", &req.in_app),
+    ];
+    let localized = req.locales.values().flat_map(|t| {
+        [
+            ("subject.txt", &t.subject),
+            ("email.html", &t.html),
+            ("email.txt", &t.text),
+            ("sms.txt", &t.sms),
+            ("in_app.txt", &t.in_app),
+        ]
+    });
+    for (name, source) in sources.into_iter().chain(localized) {
+        env().template_from_named_str(name, source)?;
+    }
+    Ok(())
+}
+
+impl Body {
+    fn of(self, t: &LocalizedTemplate) -> &str {
+        match self {
+            Body::Subject => &t.subject,
+            Body::Html => &t.html,
+            Body::Text => &t.text,
+            Body::Sms => &t.sms,
+            Body::InApp => &t.in_app,
+        }
+    }
+
+    fn of_default(self, t: &Template) -> &str {
+        match self {
+            Body::Subject => &t.subject,
+            Body::Html => &t.html,
+            Body::Text => &t.text,
+            Body::Sms => &t.sms,
+            Body::InApp => &t.in_app,
+        }
+    }
+}
+
+fn finish(name: &str, ret: Result<String, minijinja::Error>) -> Option<String> {
+    match ret {
+        Ok(body) => Some(body.trim().to_string()),
+        Err(e) => {
+            warn!("Failed to render template {}: {:?}", name, e);
+            None
+        }
+    }
+}
+
+impl<'a> From<&'a Content> for Card<'a> {
+    fn from(content: &'a Content) -> Self {
+        Card {
+            name: &content.name,
+            description: &content.description,
+            image: &content.image,
+            url: &content.url,
+            publishers: content.publishers.iter().map(|p| p.name.as_str()).collect(),
+        }
+    }
+}
+
+fn env() -> &'static Environment<'static> {
+    static ENV: OnceLock<Environment<'static>> = OnceLock::new();
+    ENV.get_or_init(|| {
+        let mut env = Environment::new();
+        for (name, source) in TEMPLATES {
+            env.add_template(name, source)
+                .expect("embedded template should be valid");
+        }
+        env
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::Publisher;
+
+    fn contents() -> Vec<Content> {
+        (1..=4)
+            .map(|id| Content {
+                id,
+                name: format!("Movie {}", id),
+                description: "A <great> movie".to_string(),
+                url: format!("https://acme.org/contents/{}", id),
+                image: format!("https://acme.org/images/{}.png", id),
+                publishers: vec![Publisher {
+                    id: 1,
+                    name: "Alice".to_string(),
+                    avatar: String::new(),
+                }],
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn email_bodies_should_render_every_card() {
+        let contents = contents();
+        let tpl = Tpl::new(&contents);
+
+        let html = tpl.to_html("Welcome");
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(r#"alt="Movie 4""#));
+        assert!(html.contains("A &lt;great&gt; movie"));
+        assert!(html.contains("by Alice"));
+
+        let text = tpl.to_text("Welcome");
+        assert!(text.starts_with("Welcome\n"));
+        assert!(text.contains("4. Movie 4\nA <great> movie\nby Alice\nhttps://acme.org/contents/4"));
+    }
+
+    #[test]
+    fn short_bodies_should_be_short() {
+        let contents = contents();
+        let tpl = Tpl::new(&contents);
+
+        assert_eq!(
+            tpl.to_sms(),
+            "Movie 1 https://acme.org/contents/1 (+3 more)"
+        );
+        assert_eq!(tpl.to_in_app(), "Movie 1, Movie 2, Movie 3 and 1 more");
+        assert_eq!(Tpl::new(&contents[..1]).to_in_app(), "Movie 1");
+        assert_eq!(Tpl::new(&[]).to_sms(), "");
+    }
+
+    #[test]
+    fn stored_template_should_override_builtin_bodies() {
+        let contents = contents();
+        let template = Template {
+            subject: "{{ cards | length }} picks for you".to_string(),
+            sms: "{{ subject }}{% for card in cards %} {{ card.name }}{% endfor %}".to_string(),
+            ..Default::default()
+        };
+        let tpl = Tpl::new(&contents[..2]).with_template(Some(&template));
+
+        assert_eq!(tpl.subject("Welcome"), "2 picks for you");
+        assert_eq!(tpl.to_sms(), "Movie 1 Movie 2");
+        assert_eq!(tpl.to_in_app(), "Movie 1, Movie 2");
+    }
+
+    #[test]
+    fn failing_stored_bodies_should_fall_back_to_builtin() {
+        let contents = contents();
+        // compiles, so it passes validation, but the filter doesn't exist
+        let template = Template {
+            subject: "{{ subject | shout }}".to_string(),
+            sms: "{{ cards | shout }}".to_string(),
+            ..Default::default()
+        };
+        let tpl = Tpl::new(&contents[..1]).with_template(Some(&template));
+
+        assert_eq!(tpl.subject("Welcome"), "Welcome");
+        assert_eq!(tpl.to_sms(), "Movie 1 https://acme.org/contents/1");
+    }
+
+    #[test]
+    fn bodies_should_be_rendered_per_recipient() {
+        let contents = contents();
+        let template = Template {
+            sms: "{{ user.name }}{% if user.gender == \"female\" %} (Ms){% endif %}: \
+                  {{ unfinished | map(attribute=\"name\") | join(\", \") }}"
+                .to_string(),
+            ..Default::default()
+        };
+        let recipient = Recipient {
+            name: "Alice",
+            gender: "female",
+            unfinished: &contents[2..],
+        };
+        let tpl = Tpl::new(&contents)
+            .with_template(Some(&template))
+            .with_recipient(recipient);
+
+        assert_eq!(tpl.to_sms(), "Alice (Ms): Movie 3, Movie 4");
+        assert_eq!(
+            Tpl::new(&contents).with_recipient(recipient).to_in_app(),
+            Tpl::new(&contents).to_in_app()
+        );
+    }
+
+    #[test]
+    fn locale_fallbacks_should_end_with_default_locale() {
+        let chain: Vec<_> = locale_fallbacks("zh-Hant-TW").collect();
+        assert_eq!(chain, ["zh-Hant-TW", "zh-Hant", "zh", "en"]);
+        assert_eq!(
+            locale_fallbacks("en-US").collect::<Vec<_>>(),
+            ["en-US", "en"]
+        );
+        assert_eq!(locale_fallbacks("").collect::<Vec<_>>(), ["en"]);
+    }
+
+    #[test]
+    fn builtin_bodies_should_be_localized() {
+        let contents = contents();
+        let tpl = Tpl::new(&contents).with_locale("zh-CN");
+
+        assert_eq!(tpl.subject("Welcome"), "欢迎");
+        assert_eq!(tpl.subject("Hello"), "Hello");
+        assert_eq!(
+            tpl.to_sms(),
+            "Movie 1 https://acme.org/contents/1（另有 3 部）"
+        );
+        assert_eq!(tpl.to_in_app(), "Movie 1、Movie 2、Movie 3 等 4 部");
+        assert!(tpl.to_html("欢迎").contains("发布者：Alice"));
+        assert!(tpl.to_text("欢迎").starts_with("欢迎\n"));
+
+        let tpl = Tpl::new(&contents).with_locale("fr");
+        assert_eq!(tpl.subject("Welcome"), "Welcome");
+        assert_eq!(tpl.to_in_app(), "Movie 1, Movie 2, Movie 3 and 1 more");
+    }
+
+    #[test]
+    fn localized_bodies_should_follow_fallback_chain() {
+        let contents = contents();
+        let localized = |subject: &str, sms: &str| LocalizedTemplate {
+            subject: subject.to_string(),
+            sms: sms.to_string(),
+            ..Default::default()
+        };
+        let template = Template {
+            subject: "Default".to_string(),
+            locales: [
+                (
+                    "zh".to_string(),
+                    localized("欢迎", "{{ cards | length }} 部新片"),
+                ),
+                ("zh-TW".to_string(), localized("歡迎", "")),
+                ("en".to_string(), localized("Welcome", "")),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let tpl = |locale| {
+            Tpl::new(&contents)
+                .with_template(Some(&template))
+                .with_locale(locale)
+        };
+
+        assert_eq!(tpl("zh-TW").subject(""), "歡迎");
+        assert_eq!(tpl("zh-TW").to_sms(), "4 部新片");
+        assert_eq!(tpl("zh-CN").subject(""), "欢迎");
+        assert_eq!(tpl("fr").subject(""), "Welcome");
+        assert_eq!(
+            tpl("fr").to_sms(),
+            "Movie 1 https://acme.org/contents/1 (+3 more)"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use crate::pb::{CreateTemplateRequest, LocalizedTemplate, Template};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use prost_types::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+    sync::RwLock,
+};
+use tonic::Status;
+
+/// versioned templates, kept in memory and persisted as one yaml file per template
+pub struct TemplateStore {
+    dir: PathBuf,
+    templates: RwLock<HashMap<String, TemplateFile>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TemplateFile {
+    published: Option<u32>,
+    versions: Vec<TemplateVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TemplateVersion {
+    version: u32,
+    subject: String,
+    html: String,
+    text: String,
+    sms: String,
+    in_app: String,
+    created_at: DateTime<Utc>,
+    #[serde(default)]
+    locales: BTreeMap<String, LocalizedTemplate>,
+}
+
+// the errors are returned as is by the rpc handlers
+#[allow(clippy::result_large_err)]
+impl TemplateStore {
+    /// load every template stored in `dir`, the directory is created if missing
+    pub fn load(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut templates = HashMap::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "yml") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let file: TemplateFile = serde_yaml::from_reader(fs::File::open(&path)?)?;
+            templates.insert(name.to_string(), file);
+        }
+
+        Ok(Self {
+            dir,
+            templates: RwLock::new(templates),
+        })
+    }
+
+    /// add a new version of the template, versions start at 1
+    pub fn create(&self, req: CreateTemplateRequest) -> Result<Template, Status> {
+        validate_name(&req.name)?;
+        if let Some(locale) = req.locales.keys().find(|locale| !is_locale(locale)) {
+            return Err(Status::invalid_argument(format!(
+                "Invalid template locale: {:?}",
+                locale
+            )));
+        }
+        super::validate(&req)
+            .map_err(|e| Status::invalid_argument(format!("Invalid template: {}", e)))?;
+
+        let mut templates = self.templates.write().unwrap();
+        let mut file = templates.get(&req.name).cloned().unwrap_or_default();
+        let version = file.versions.last().map_or(1, |v| v.version + 1);
+        file.versions.push(TemplateVersion {
+            version,
+            subject: req.subject,
+            html: req.html,
+            text: req.text,
+            sms: req.sms,
+            in_app: req.in_app,
+            created_at: Utc::now(),
+            locales: req.locales.into_iter().collect(),
+        });
+        self.save(&req.name, &file)?;
+
+        let template = file.to_template(&req.name, version);
+        templates.insert(req.name, file);
+        template.ok_or_else(|| Status::internal("Template version not saved"))
+    }
+
+    /// get a version of the template, the published one if version is 0
+    pub fn get(&self, name: &str, version: u32) -> Result<Template, Status> {
+        let templates = self.templates.read().unwrap();
+        let file = templates
+            .get(name)
+            .ok_or_else(|| Status::not_found(format!("Template {} not found", name)))?;
+        let version = match version {
+            0 => file.published.ok_or_else(|| {
+                Status::not_found(format!("Template {} has no published version", name))
+            })?,
+            v => v,
+        };
+        file.to_template(name, version).ok_or_else(|| {
+            Status::not_found(format!("Template {} version {} not found", name, version))
+        })
+    }
+
+    /// every version of the template, or the latest version of every template if name is empty
+    pub fn list(&self, name: &str) -> Result<Vec<Template>, Status> {
+        let templates = self.templates.read().unwrap();
+        if !name.is_empty() {
+            let file = templates
+                .get(name)
+                .ok_or_else(|| Status::not_found(format!("Template {} not found", name)))?;
+            return Ok(file
+                .versions
+                .iter()
+                .map(|v| v.to_template(name, file.published))
+                .collect());
+        }
+
+        let mut ret: Vec<_> = templates
+            .iter()
+            .filter_map(|(name, file)| {
+                let latest = file.versions.last()?;
+                Some(latest.to_template(name, file.published))
+            })
+            .collect();
+        ret.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(ret)
+    }
+
+    /// make the version the one returned when no version is asked for
+    pub fn publish(&self, name: &str, version: u32) -> Result<Template, Status> {
+        let mut templates = self.templates.write().unwrap();
+        let file = templates
+            .get(name)
+            .ok_or_else(|| Status::not_found(format!("Template {} not found", name)))?;
+        if !file.versions.iter().any(|v| v.version == version) {
+            return Err(Status::not_found(format!(
+                "Template {} version {} not found",
+                name, version
+            )));
+        }
+
+        let mut file = file.clone();
+        file.published = Some(version);
+        self.save(name, &file)?;
+
+        let template = file.to_template(name, version);
+        templates.insert(name.to_string(), file);
+        template.ok_or_else(|| Status::internal("Template version not saved"))
+    }
+
+    /// write the template to a temporary file first so a crash never leaves a partial file
+    fn save(&self, name: &str, file: &TemplateFile) -> Result<(), Status> {
+        let path = self.path(name);
+        let tmp = path.with_extension("yml.tmp");
+        let data = serde_yaml::to_string(file)
+            .map_err(|e| Status::internal(format!("Failed to serialize template: {}", e)))?;
+        fs::write(&tmp, data)
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| Status::internal(format!("Failed to save template {}: {}", name, e)))
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.yml", name))
+    }
+}
+
+impl TemplateFile {
+    fn to_template(&self, name: &str, version: u32) -> Option<Template> {
+        self.versions
+            .iter()
+            .find(|v| v.version == version)
+            .map(|v| v.to_template(name, self.published))
+    }
+}
+
+impl TemplateVersion {
+    fn to_template(&self, name: &str, published: Option<u32>) -> Template {
+        Template {
+            name: name.to_string(),
+            version: self.version,
+            subject: self.subject.clone(),
+            html: self.html.clone(),
+            text: self.text.clone(),
+            sms: self.sms.clone(),
+            in_app: self.in_app.clone(),
+            published: published == Some(self.version),
+            created_at: Some(Timestamp {
+                seconds: self.created_at.timestamp(),
+                nanos: self.created_at.timestamp_subsec_nanos() as i32,
+            }),
+            locales: self
+                .locales
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// names are used as file names, so only allow lowercase letters, digits, `-` and `_`
+#[allow(clippy::result_large_err)]
+fn validate_name(name: &str) -> Result<(), Status> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Status::invalid_argument(format!(
+            "Invalid template name: {:?}",
+            name
+        )))
+    }
+}
+
+/// locales are tags like zh-CN
+fn is_locale(locale: &str) -> bool {
+    !locale.is_empty()
+        && locale
+            .split('-')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use tonic::Code;
+
+    fn temp_dir() -> PathBuf {
+        let suffix: u64 = rand::thread_rng().gen();
+        std::env::temp_dir().join(format!("crm-metadata-templates-{:x}", suffix))
+    }
+
+    fn request(name: &str, sms: &str) -> CreateTemplateRequest {
+        CreateTemplateRequest {
+            name: name.to_string(),
+            sms: sms.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn template_versions_should_be_published_and_persisted() -> Result<()> {
+        let dir = temp_dir();
+        let store = TemplateStore::load(&dir)?;
+
+        let v1 = store.create(request("welcome", "v1"))?;
+        let v2 = store.create(request("welcome", "v2"))?;
+        assert_eq!((v1.version, v2.version), (1, 2));
+        assert_eq!(store.get("welcome", 0).unwrap_err().code(), Code::NotFound);
+
+        let published = store.publish("welcome", 1)?;
+        assert!(published.published);
+        assert_eq!(store.get("welcome", 0)?.sms, "v1");
+        assert_eq!(store.get("welcome", 2)?.sms, "v2");
+        assert_eq!(
+            store.publish("welcome", 3).unwrap_err().code(),
+            Code::NotFound
+        );
+
+        let store = TemplateStore::load(&dir)?;
+        assert_eq!(store.list("welcome")?.len(), 2);
+        assert_eq!(store.get("welcome", 0)?.version, 1);
+        let latest = store.list("")?;
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].version, 2);
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_templates_should_be_rejected() -> Result<()> {
+        let dir = temp_dir();
+        let store = TemplateStore::load(&dir)?;
+
+        let err = store.create(request("../welcome", "hi")).unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+        let err = store.create(request("welcome", "{% for %}")).unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+        let mut req = request("welcome", "hi");
+        req.locales.insert("zh_CN".to_string(), Default::default());
+        assert_eq!(store.create(req).unwrap_err().code(), Code::InvalidArgument);
+        assert!(store.list("")?.is_empty());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use crm_metadata::{
     AppConfig, MetadataService,
 };
 use futures::StreamExt;
-use std::{net::SocketAddr, time::Duration};
+use std::{env, net::SocketAddr, time::Duration};
 use tokio::time::sleep;
 use tonic::{transport::Server, Request};
 
@@ -27,10 +27,11 @@ async fn test_metadata() -> Result<()> {
 }
 
 async fn start_server() -> Result<SocketAddr> {
-    let config = AppConfig::load()?;
+    let mut config = AppConfig::load()?;
+    config.templates.dir = env::temp_dir().join("crm-metadata-test-templates");
     let addr = format!("[::1]:{}", config.server.port).parse()?;
 
-    let svc = MetadataService::new(config).into_server();
+    let svc = MetadataService::new(config)?.into_server();
     tokio::spawn(async move {
         Server::builder()
             .add_service(svc)
@@ -0,0 +1,26 @@
+-- Add migration script here
+CREATE TYPE outbox_status AS ENUM(
+  'queued',
+  'sending',
+  'delivered',
+  'failed'
+);
+
+CREATE TABLE outbox(
+  id bigserial PRIMARY KEY,
+  message_id varchar(64) NOT NULL,
+  -- protobuf encoded SendRequest
+  payload bytea NOT NULL,
+  status outbox_status NOT NULL DEFAULT 'queued',
+  attempts int NOT NULL DEFAULT 0,
+  locked_until timestamptz,
+  last_error text,
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX outbox_message_id_idx ON outbox(message_id);
+
+CREATE INDEX outbox_pending_idx ON outbox(id)
+WHERE
+  status IN ('queued', 'sending');
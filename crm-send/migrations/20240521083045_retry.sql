@@ -0,0 +1,14 @@
+-- Add migration script here
+ALTER TABLE outbox
+  ADD COLUMN next_attempt_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+DROP INDEX outbox_pending_idx;
+
+CREATE INDEX outbox_pending_idx ON outbox(next_attempt_at)
+WHERE
+  status IN ('queued', 'sending');
+
+-- failed messages are the dead letters
+CREATE INDEX outbox_failed_idx ON outbox(updated_at)
+WHERE
+  status = 'failed';
@@ -0,0 +1,7 @@
+-- Add migration script here
+-- the provider rejected every recipient of the message
+ALTER TYPE outbox_status ADD VALUE 'bounced';
+
+-- protobuf encoded RecipientResults
+ALTER TABLE outbox
+  ADD COLUMN results bytea;
@@ -0,0 +1,11 @@
+-- Add migration script here
+-- responses of the messages sent recently, a message_id sent again within the
+-- dedup window gets the stored response back instead of being sent twice
+CREATE TABLE dedup(
+  message_id varchar(64) PRIMARY KEY,
+  -- protobuf encoded SendResponse, NULL while the message is being accepted
+  response bytea,
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX dedup_created_at_idx ON dedup(created_at);
@@ -0,0 +1,89 @@
+use crate::{
+    pb::{
+        DeadLetter, GetDeadLetterRequest, ListDeadLettersRequest, ListDeadLettersResponse,
+        PurgeDeadLettersRequest, PurgeDeadLettersResponse, RequeueDeadLettersRequest,
+        RequeueDeadLettersResponse,
+    },
+    NotificationService, ServiceResult,
+};
+use tonic::{Response, Status};
+use tracing::{info, warn};
+
+const DEFAULT_LIMIT: usize = 100;
+
+impl NotificationService {
+    pub async fn list_dead_letters(
+        &self,
+        req: ListDeadLettersRequest,
+    ) -> ServiceResult<ListDeadLettersResponse> {
+        let limit = match req.limit {
+            0 => DEFAULT_LIMIT,
+            n => n as usize,
+        };
+        let dead_letters = self
+            .outbox
+            .list_dead_letters(limit, req.offset as usize)
+            .await
+            .map_err(internal)?;
+        Ok(Response::new(ListDeadLettersResponse { dead_letters }))
+    }
+
+    pub async fn get_dead_letter(&self, req: GetDeadLetterRequest) -> ServiceResult<DeadLetter> {
+        match self
+            .outbox
+            .get_dead_letter(&req.message_id)
+            .await
+            .map_err(internal)?
+        {
+            Some(dead_letter) => Ok(Response::new(dead_letter)),
+            None => Err(Status::not_found(format!(
+                "dead letter {} not found",
+                req.message_id
+            ))),
+        }
+    }
+
+    pub async fn requeue_dead_letters(
+        &self,
+        req: RequeueDeadLettersRequest,
+    ) -> ServiceResult<RequeueDeadLettersResponse> {
+        let count = self
+            .outbox
+            .requeue_dead_letters(&req.message_ids)
+            .await
+            .map_err(internal)?;
+        info!("Requeued {} dead letters", count);
+        self.notify.notify_one();
+        if let Ok(statuses) = self.outbox.get_status(&req.message_ids).await {
+            statuses.into_iter().for_each(|status| self.publish(status));
+        }
+        Ok(Response::new(RequeueDeadLettersResponse { count }))
+    }
+
+    pub async fn purge_dead_letters(
+        &self,
+        req: PurgeDeadLettersRequest,
+    ) -> ServiceResult<PurgeDeadLettersResponse> {
+        let ids = if req.all {
+            None
+        } else if req.message_ids.is_empty() {
+            return Err(Status::invalid_argument(
+                "message_ids is required unless all is set",
+            ));
+        } else {
+            Some(req.message_ids.as_slice())
+        };
+        let count = self
+            .outbox
+            .purge_dead_letters(ids)
+            .await
+            .map_err(internal)?;
+        info!("Purged {} dead letters", count);
+        Ok(Response::new(PurgeDeadLettersResponse { count }))
+    }
+}
+
+fn internal(e: anyhow::Error) -> Status {
+    warn!("Failed to access dead letters: {:?}", e);
+    Status::internal("Failed to access dead letters")
+}
@@ -1,22 +1,13 @@
-use super::{to_ts, Sender};
+use super::Sender;
 use crate::{
     pb::{send_request::Msg, EmailMessage, SendRequest, SendResponse},
     NotificationService,
 };
 use tonic::Status;
-use tracing::warn;
 
 impl Sender for EmailMessage {
     async fn send(self, svc: NotificationService) -> Result<SendResponse, Status> {
-        let message_id = self.message_id.clone();
-        svc.sender.send(Msg::Email(self)).await.map_err(|e| {
-            warn!("Failed to send message: {:?}", e);
-            Status::internal("Failed to send message")
-        })?;
-        Ok(SendResponse {
-            message_id,
-            timestamp: Some(to_ts()),
-        })
+        svc.enqueue(Msg::Email(self)).await
     }
 }
 
@@ -45,6 +36,7 @@ impl EmailMessage {
             recipients: vec![SafeEmail().fake()],
             subject: "Hello".to_string(),
             body: "Hello, world!".to_string(),
+            html_body: "<p>Hello, world!</p>".to_string(),
         }
     }
 }
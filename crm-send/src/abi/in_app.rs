@@ -1,22 +1,26 @@
-use super::{to_ts, Sender};
+use super::Sender;
 use crate::{
-    pb::{send_request::Msg, InAppMessage, SendRequest, SendResponse},
-    NotificationService,
+    pb::{send_request::Msg, InAppMessage, SendRequest, SendResponse, SubscribeRequest},
+    InAppStream, NotificationService, ServiceResult,
 };
-use tonic::Status;
-use tracing::warn;
+use tonic::{Response, Status};
+use tracing::info;
+
+impl NotificationService {
+    pub async fn subscribe(&self, req: SubscribeRequest) -> ServiceResult<InAppStream> {
+        if req.device_id.is_empty() {
+            return Err(Status::invalid_argument("device_id is required"));
+        }
+
+        info!("Device {} subscribed", req.device_id);
+        let stream = self.transports.in_app.subscribe(&req.device_id);
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
 
 impl Sender for InAppMessage {
     async fn send(self, svc: NotificationService) -> Result<SendResponse, Status> {
-        let message_id = self.message_id.clone();
-        svc.sender.send(Msg::InApp(self)).await.map_err(|e| {
-            warn!("Failed to send message: {:?}", e);
-            Status::internal("Failed to send message")
-        })?;
-        Ok(SendResponse {
-            message_id,
-            timestamp: Some(to_ts()),
-        })
+        svc.enqueue(Msg::InApp(self)).await
     }
 }
 
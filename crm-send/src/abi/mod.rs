@@ -1,13 +1,16 @@
+mod dead_letter;
 mod email;
 mod in_app;
 mod sms;
+mod status;
 
-use chrono::Utc;
-use crm_metadata::{pb::Content, Tpl};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crm_metadata::Tpl;
 use futures::{Stream, StreamExt};
 use prost_types::Timestamp;
-use std::{ops::Deref, sync::Arc, time::Duration};
-use tokio::{sync::mpsc, time::sleep};
+use std::{ops::Deref, sync::Arc};
+use tokio::sync::{broadcast, mpsc, Notify};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Response, Status};
 use tracing::{info, warn};
@@ -16,10 +19,12 @@ use uuid::Uuid;
 const CHANNEL_SIZE: usize = 1024;
 
 use crate::{
+    outbox::{spawn_workers, Outbox, Reservation},
     pb::{
-        notification_server::NotificationServer, send_request::Msg, EmailMessage, SendRequest,
-        SendResponse,
+        notification_server::NotificationServer, send_request::Msg, DeliveryStatus, EmailMessage,
+        InAppMessage, MessageStatus, SendRequest, SendResponse, SmsMessage,
     },
+    transport::Transports,
     AppConfig, NotificationService, NotificationServiceInner, ResponseStream, ServiceResult,
 };
 
@@ -28,12 +33,40 @@ pub trait Sender {
 }
 
 impl NotificationService {
-    pub fn new(config: AppConfig) -> Self {
-        let sender = dummy_send();
-        let inner = NotificationServiceInner { config, sender };
-        Self {
+    pub async fn new(config: AppConfig) -> Result<Self> {
+        let outbox = config
+            .outbox
+            .outbox()
+            .await
+            .context("Failed to create outbox")?;
+        Self::with_outbox(config, outbox)
+    }
+
+    /// create the service on top of an existing outbox and start draining it
+    pub fn with_outbox(config: AppConfig, outbox: Arc<dyn Outbox>) -> Result<Self> {
+        let transports = Transports::try_new(&config).context("Failed to create transports")?;
+        let transports = Arc::new(transports);
+        let notify = Arc::new(Notify::new());
+        let (updates, _) = broadcast::channel(CHANNEL_SIZE);
+        spawn_workers(
+            &config.outbox,
+            &config.retry,
+            outbox.clone(),
+            transports.clone(),
+            notify.clone(),
+            updates.clone(),
+        );
+
+        let inner = NotificationServiceInner {
+            config,
+            outbox,
+            notify,
+            transports,
+            updates,
+        };
+        Ok(Self {
             inner: Arc::new(inner),
-        }
+        })
     }
 
     pub fn into_server(self) -> NotificationServer<Self> {
@@ -48,11 +81,8 @@ impl NotificationService {
         let notif = self.clone();
         tokio::spawn(async move {
             while let Some(Ok(req)) = stream.next().await {
-                let notif_clone = notif.clone();
                 let res = match req.msg {
-                    Some(Msg::Email(email)) => email.send(notif_clone).await,
-                    Some(Msg::Sms(sms)) => sms.send(notif_clone).await,
-                    Some(Msg::InApp(in_app)) => in_app.send(notif_clone).await,
+                    Some(msg) => notif.dispatch(msg).await,
                     None => {
                         warn!("Invalid request");
                         Err(Status::invalid_argument("Invalid request"))
@@ -67,6 +97,87 @@ impl NotificationService {
     }
 }
 
+impl NotificationService {
+    /// send the message once, a message_id seen within the dedup window gets its original
+    /// response back without being sent again
+    async fn dispatch(&self, msg: Msg) -> Result<SendResponse, Status> {
+        let window = self.config.outbox.dedup_window();
+        let message_id = msg.message_id().to_string();
+        if window.is_zero() || message_id.is_empty() {
+            return self.send_msg(msg).await;
+        }
+
+        let reservation = self
+            .outbox
+            .reserve(&message_id, window)
+            .await
+            .map_err(|e| {
+                warn!("Failed to reserve message {}: {:?}", message_id, e);
+                Status::internal("Failed to send message")
+            })?;
+        match reservation {
+            Reservation::Reserved => {}
+            Reservation::Sent(response) => {
+                info!("Message {} has been sent already", message_id);
+                return Ok(response);
+            }
+            Reservation::Pending => {
+                return Err(Status::aborted(format!(
+                    "message {} is being sent, retry later",
+                    message_id
+                )));
+            }
+        }
+
+        let ret = self.send_msg(msg).await;
+        let stored = match &ret {
+            Ok(response) => self.outbox.remember(response).await,
+            Err(_) => self.outbox.release(&message_id).await,
+        };
+        if let Err(e) = stored {
+            warn!("Failed to update reservation of {}: {:?}", message_id, e);
+        }
+        ret
+    }
+
+    async fn send_msg(&self, msg: Msg) -> Result<SendResponse, Status> {
+        let svc = self.clone();
+        match msg {
+            Msg::Email(email) => email.send(svc).await,
+            Msg::Sms(sms) => sms.send(svc).await,
+            Msg::InApp(in_app) => in_app.send(svc).await,
+        }
+    }
+
+    /// durably accept the message, it is delivered later by the outbox workers
+    async fn enqueue(&self, msg: Msg) -> Result<SendResponse, Status> {
+        let message_id = msg.message_id().to_string();
+        let accepted_at = self.outbox.push(&msg).await.map_err(|e| {
+            warn!("Failed to enqueue message: {:?}", e);
+            Status::internal("Failed to send message")
+        })?;
+        self.notify.notify_one();
+        self.publish(MessageStatus {
+            message_id: message_id.clone(),
+            status: DeliveryStatus::Queued as i32,
+            updated_at: Some(to_ts(accepted_at)),
+            ..Default::default()
+        });
+
+        Ok(SendResponse {
+            message_id,
+            timestamp: Some(to_ts(accepted_at)),
+            results: vec![],
+            status: DeliveryStatus::Queued as i32,
+        })
+    }
+
+    /// notify the status watchers, nobody watching is not an error
+    fn publish(&self, status: MessageStatus) {
+        let _ = self.updates.send(status);
+    }
+}
+
 impl Deref for NotificationService {
     type Target = NotificationServiceInner;
 
@@ -76,58 +187,69 @@ impl Deref for NotificationService {
 }
 
 impl SendRequest {
-    pub fn new(
-        subject: String,
-        sender: String,
-        recipients: &[String],
-        contents: &[Content],
-    ) -> Self {
-        let tpl = Tpl(contents);
+    pub fn new_email(subject: &str, sender: String, recipients: &[String], tpl: &Tpl) -> Self {
+        let subject = tpl.subject(subject);
         let msg = Msg::Email(EmailMessage {
             message_id: Uuid::new_v4().to_string(),
+            body: tpl.to_text(&subject),
+            html_body: tpl.to_html(&subject),
             subject,
             sender,
             recipients: recipients.to_vec(),
-            body: tpl.to_body(),
         });
 
         SendRequest { msg: Some(msg) }
     }
-}
 
-fn dummy_send() -> mpsc::Sender<Msg> {
-    let (tx, mut rx) = mpsc::channel(CHANNEL_SIZE * 100);
+    pub fn new_sms(sender: String, recipients: &[String], tpl: &Tpl) -> Self {
+        let msg = Msg::Sms(SmsMessage {
+            message_id: Uuid::new_v4().to_string(),
+            sender,
+            recipients: recipients.to_vec(),
+            body: tpl.to_sms(),
+        });
+
+        SendRequest { msg: Some(msg) }
+    }
+
+    pub fn new_in_app(title: &str, device_id: String, tpl: &Tpl) -> Self {
+        let msg = Msg::InApp(InAppMessage {
+            message_id: Uuid::new_v4().to_string(),
+            device_id,
+            title: tpl.subject(title),
+            body: tpl.to_in_app(),
+        });
 
-    tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            info!("Sending message: {:?}", msg);
-            sleep(Duration::from_millis(300)).await;
+        SendRequest { msg: Some(msg) }
+    }
+}
+
+impl Msg {
+    pub fn message_id(&self) -> &str {
+        match self {
+            Msg::Email(email) => &email.message_id,
+            Msg::Sms(sms) => &sms.message_id,
+            Msg::InApp(in_app) => &in_app.message_id,
         }
-    });
-    tx
+    }
 }
 
-fn to_ts() -> Timestamp {
-    let now = Utc::now();
+pub(crate) fn to_ts(dt: DateTime<Utc>) -> Timestamp {
     Timestamp {
-        seconds: now.timestamp(),
-        nanos: now.timestamp_subsec_nanos() as i32,
+        seconds: dt.timestamp(),
+        nanos: dt.timestamp_subsec_nanos() as i32,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{
-        pb::{EmailMessage, InAppMessage, SmsMessage},
-        AppConfig,
-    };
-    use anyhow::Result;
+    use crate::AppConfig;
 
     #[tokio::test]
     async fn send_should_work() -> Result<()> {
-        let config = AppConfig::load()?;
-        let service = NotificationService::new(config);
+        let config = AppConfig::load_for_test()?;
+        let service = NotificationService::new(config).await?;
         let stream = tokio_stream::iter(vec![
             Ok(EmailMessage::fake().into()),
             Ok(SmsMessage::fake().into()),
@@ -1,6 +1,7 @@
 use super::{to_ts, Sender};
 use crate::{
-    pb::{send_request::Msg, SendRequest, SendResponse, SmsMessage},
+    pb::{send_request::Msg, DeliveryStatus, MessageStatus, SendRequest, SendResponse, SmsMessage},
+    transport::{is_permanent, retry_after},
     NotificationService,
 };
 use tonic::Status;
@@ -8,18 +9,68 @@ use tracing::warn;
 
 impl Sender for SmsMessage {
     async fn send(self, svc: NotificationService) -> Result<SendResponse, Status> {
-        let message_id = self.message_id.clone();
-        svc.sender.send(Msg::Sms(self)).await.map_err(|e| {
-            warn!("Failed to send message: {:?}", e);
+        // persist the message before calling the provider, so it isn't lost if we crash
+        // in between; the outbox workers take it over once its lease expires
+        let msg = Msg::Sms(self.clone());
+        let (id, accepted_at) = svc.outbox.push_claimed(&msg).await.map_err(|e| {
+            warn!("Failed to store message: {:?}", e);
             Status::internal("Failed to send message")
         })?;
+        let message_id = self.message_id.clone();
+
+        let (status, results, error) = match svc.transports.sms.send(&self).await {
+            Ok(results) => {
+                let status = DeliveryStatus::from_results(&results);
+                svc.outbox
+                    .complete(id, status, &results)
+                    .await
+                    .map_err(|e| update_failed(&message_id, e))?;
+                (status, results, None)
+            }
+            Err(e) if is_permanent(&e) => {
+                warn!("Failed to send message: {:?}", e);
+                svc.outbox
+                    .fail(id, &e.to_string())
+                    .await
+                    .map_err(|e| update_failed(&message_id, e))?;
+                return Err(Status::invalid_argument(e.to_string()));
+            }
+            Err(e) => {
+                // provider is unavailable, let the outbox workers retry it
+                let delay =
+                    retry_after(&e).unwrap_or_else(|| svc.config.retry.policy(&msg).backoff(1));
+                warn!("Failed to send message, retry in {:?}: {:?}", delay, e);
+                svc.outbox
+                    .retry(id, &e.to_string(), delay)
+                    .await
+                    .map_err(|e| update_failed(&message_id, e))?;
+                (DeliveryStatus::Queued, vec![], Some(e.to_string()))
+            }
+        };
+
+        svc.publish(MessageStatus {
+            message_id: message_id.clone(),
+            status: status as i32,
+            attempts: 1,
+            last_error: error.unwrap_or_default(),
+            results: results.clone(),
+            updated_at: Some(to_ts(accepted_at)),
+        });
+
         Ok(SendResponse {
             message_id,
-            timestamp: Some(to_ts()),
+            timestamp: Some(to_ts(accepted_at)),
+            results,
+            status: status as i32,
         })
     }
 }
 
+fn update_failed(message_id: &str, e: anyhow::Error) -> Status {
+    warn!("Failed to update message {}: {:?}", message_id, e);
+    Status::internal("Failed to send message")
+}
+
 impl From<SmsMessage> for Msg {
     fn from(sms: SmsMessage) -> Self {
         Msg::Sms(sms)
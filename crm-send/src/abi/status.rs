@@ -0,0 +1,74 @@
+use super::CHANNEL_SIZE;
+use crate::{
+    pb::{GetStatusRequest, GetStatusResponse, WatchStatusRequest},
+    NotificationService, ServiceResult, StatusStream,
+};
+use std::collections::HashSet;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Response, Status};
+use tracing::warn;
+
+impl NotificationService {
+    pub async fn get_status(&self, req: GetStatusRequest) -> ServiceResult<GetStatusResponse> {
+        if req.message_ids.is_empty() {
+            return Err(Status::invalid_argument("message_ids is required"));
+        }
+
+        let statuses = self
+            .outbox
+            .get_status(&req.message_ids)
+            .await
+            .map_err(|e| {
+                warn!("Failed to get message status: {:?}", e);
+                Status::internal("Failed to get message status")
+            })?;
+        Ok(Response::new(GetStatusResponse { statuses }))
+    }
+
+    pub async fn watch_status(&self, req: WatchStatusRequest) -> ServiceResult<StatusStream> {
+        // subscribe before reading the current statuses so no change is missed in between
+        let mut rx = self.updates.subscribe();
+        let current = if req.message_ids.is_empty() {
+            vec![]
+        } else {
+            self.get_status(GetStatusRequest {
+                message_ids: req.message_ids.clone(),
+            })
+            .await?
+            .into_inner()
+            .statuses
+        };
+
+        let ids: HashSet<String> = req.message_ids.into_iter().collect();
+        let (tx, out) = mpsc::channel(CHANNEL_SIZE);
+        tokio::spawn(async move {
+            for status in current {
+                if tx.send(Ok(status)).await.is_err() {
+                    return;
+                }
+            }
+
+            loop {
+                let status = tokio::select! {
+                    _ = tx.closed() => break,
+                    ret = rx.recv() => match ret {
+                        Ok(status) => status,
+                        Err(RecvError::Lagged(n)) => {
+                            warn!("Status watcher lagged behind, {} updates skipped", n);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                };
+                if (ids.is_empty() || ids.contains(&status.message_id))
+                    && tx.send(Ok(status)).await.is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(out))))
+    }
+}
@@ -14,7 +14,7 @@ async fn main() -> Result<()> {
     let addr = format!("[::1]:{}", addr).parse().unwrap();
     info!("Notification service listening on {}", addr);
 
-    let svc = NotificationService::new(config).into_server();
+    let svc = NotificationService::new(config).await?.into_server();
     Server::builder().add_service(svc).serve(addr).await?;
     Ok(())
 }
@@ -0,0 +1,395 @@
+use super::{Outbox, OutboxEntry, Reservation};
+use crate::{
+    abi::to_ts,
+    pb::{
+        send_request::Msg, DeadLetter, DeliveryStatus, MessageStatus, RecipientResult, SendRequest,
+        SendResponse,
+    },
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+use tonic::async_trait;
+
+/// how long delivered, bounced and failed messages are kept around for status queries
+const DEFAULT_RETENTION: Duration = Duration::from_secs(3600);
+
+/// non-durable outbox, messages and their statuses are lost when the process exits
+pub struct MemoryOutbox {
+    state: Mutex<MemoryState>,
+    retention: Duration,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    next_id: i64,
+    entries: BTreeMap<i64, MemoryEntry>,
+    /// queued entries by the time of their next attempt, so claiming doesn't scan everything
+    queued: BTreeSet<(DateTime<Utc>, i64)>,
+    /// entries which reached a final status, in the order they did
+    finished: VecDeque<(DateTime<Utc>, i64)>,
+    /// reserved message_ids with the time they were reserved and their response once sent
+    reservations: HashMap<String, (DateTime<Utc>, Option<SendResponse>)>,
+}
+
+struct MemoryEntry {
+    msg: Msg,
+    status: DeliveryStatus,
+    attempts: i32,
+    next_attempt_at: DateTime<Utc>,
+    last_error: Option<String>,
+    results: Vec<RecipientResult>,
+    updated_at: DateTime<Utc>,
+}
+
+impl MemoryOutbox {
+    /// delivered, bounced and failed messages are dropped `retention` after they finished
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            state: Mutex::new(MemoryState::default()),
+            retention,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, MemoryState> {
+        let mut state = self.state.lock().unwrap();
+        state.evict(self.retention);
+        state
+    }
+}
+
+impl Default for MemoryOutbox {
+    fn default() -> Self {
+        Self::new(DEFAULT_RETENTION)
+    }
+}
+
+#[async_trait]
+impl Outbox for MemoryOutbox {
+    async fn push(&self, msg: &Msg) -> Result<DateTime<Utc>> {
+        let mut state = self.state();
+        let (_, accepted_at) = state.insert(msg, DeliveryStatus::Queued, 0);
+        Ok(accepted_at)
+    }
+
+    async fn push_claimed(&self, msg: &Msg) -> Result<(i64, DateTime<Utc>)> {
+        let mut state = self.state();
+        Ok(state.insert(msg, DeliveryStatus::Sending, 1))
+    }
+
+    async fn claim(&self, limit: usize) -> Result<Vec<OutboxEntry>> {
+        let mut state = self.state();
+        let now = Utc::now();
+        let ready: Vec<_> = state
+            .queued
+            .iter()
+            .take_while(|(next_attempt_at, _)| *next_attempt_at <= now)
+            .take(limit)
+            .copied()
+            .collect();
+
+        let mut entries = Vec::with_capacity(ready.len());
+        for key in ready {
+            state.queued.remove(&key);
+            if let Some(e) = state.entries.get_mut(&key.1) {
+                e.status = DeliveryStatus::Sending;
+                e.attempts += 1;
+                e.updated_at = now;
+                entries.push(OutboxEntry {
+                    id: key.1,
+                    attempts: e.attempts,
+                    msg: e.msg.clone(),
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    async fn complete(
+        &self,
+        id: i64,
+        status: DeliveryStatus,
+        results: &[RecipientResult],
+    ) -> Result<()> {
+        let mut state = self.state();
+        let now = Utc::now();
+        if let Some(e) = state.entries.get_mut(&id) {
+            // an in-app message is completed while queued for its redelivery
+            let key = (e.next_attempt_at, id);
+            e.status = status;
+            e.results = results.to_vec();
+            e.updated_at = now;
+            state.queued.remove(&key);
+            state.finished.push_back((now, id));
+        }
+        Ok(())
+    }
+
+    async fn retry(&self, id: i64, error: &str, delay: Duration) -> Result<()> {
+        let mut state = self.state();
+        let now = Utc::now();
+        if let Some(e) = state.entries.get_mut(&id) {
+            e.status = DeliveryStatus::Queued;
+            e.next_attempt_at = now + delay;
+            e.last_error = Some(error.to_string());
+            e.updated_at = now;
+            let key = (e.next_attempt_at, id);
+            state.queued.insert(key);
+        }
+        Ok(())
+    }
+
+    async fn fail(&self, id: i64, error: &str) -> Result<()> {
+        let mut state = self.state();
+        let now = Utc::now();
+        if let Some(e) = state.entries.get_mut(&id) {
+            e.status = DeliveryStatus::Failed;
+            e.last_error = Some(error.to_string());
+            e.updated_at = now;
+            state.finished.push_back((now, id));
+        }
+        Ok(())
+    }
+
+    async fn list_dead_letters(&self, limit: usize, offset: usize) -> Result<Vec<DeadLetter>> {
+        let state = self.state();
+        let ret = state
+            .entries
+            .values()
+            .filter(|e| e.status == DeliveryStatus::Failed)
+            .sorted_by_key(|e| e.updated_at)
+            .skip(offset)
+            .take(limit)
+            .map(MemoryEntry::to_dead_letter)
+            .collect();
+        Ok(ret)
+    }
+
+    async fn get_dead_letter(&self, message_id: &str) -> Result<Option<DeadLetter>> {
+        let state = self.state();
+        let ret = state
+            .entries
+            .values()
+            .rev()
+            .find(|e| e.status == DeliveryStatus::Failed && e.msg.message_id() == message_id)
+            .map(MemoryEntry::to_dead_letter);
+        Ok(ret)
+    }
+
+    async fn requeue_dead_letters(&self, message_ids: &[String]) -> Result<u64> {
+        let mut state = self.state();
+        let now = Utc::now();
+        let mut requeued = vec![];
+        for (id, e) in state.entries.iter_mut() {
+            if e.status == DeliveryStatus::Failed
+                && message_ids.iter().any(|id| id == e.msg.message_id())
+            {
+                e.status = DeliveryStatus::Queued;
+                e.attempts = 0;
+                e.next_attempt_at = now;
+                e.updated_at = now;
+                requeued.push((now, *id));
+            }
+        }
+        let count = requeued.len() as u64;
+        state.queued.extend(requeued);
+        Ok(count)
+    }
+
+    async fn purge_dead_letters(&self, message_ids: Option<&[String]>) -> Result<u64> {
+        let mut state = self.state();
+        let before = state.entries.len();
+        state.entries.retain(|_, e| {
+            let purge = e.status == DeliveryStatus::Failed
+                && message_ids.is_none_or(|ids| ids.iter().any(|id| id == e.msg.message_id()));
+            !purge
+        });
+        Ok((before - state.entries.len()) as u64)
+    }
+
+    async fn get_status(&self, message_ids: &[String]) -> Result<Vec<MessageStatus>> {
+        let state = self.state();
+        let ret = message_ids
+            .iter()
+            .filter_map(|id| {
+                state
+                    .entries
+                    .values()
+                    .rev()
+                    .find(|e| e.msg.message_id() == id)
+                    .map(MemoryEntry::to_status)
+            })
+            .collect();
+        Ok(ret)
+    }
+
+    async fn reserve(&self, message_id: &str, window: Duration) -> Result<Reservation> {
+        let mut state = self.state();
+        let now = Utc::now();
+        match state.reservations.get(message_id) {
+            Some((reserved_at, response)) if *reserved_at + window > now => Ok(match response {
+                Some(response) => Reservation::Sent(response.clone()),
+                None => Reservation::Pending,
+            }),
+            _ => {
+                state
+                    .reservations
+                    .insert(message_id.to_string(), (now, None));
+                Ok(Reservation::Reserved)
+            }
+        }
+    }
+
+    async fn remember(&self, response: &SendResponse) -> Result<()> {
+        let mut state = self.state();
+        if let Some((_, stored)) = state.reservations.get_mut(&response.message_id) {
+            *stored = Some(response.clone());
+        }
+        Ok(())
+    }
+
+    async fn release(&self, message_id: &str) -> Result<()> {
+        self.state().reservations.remove(message_id);
+        Ok(())
+    }
+
+    async fn expire_reservations(&self, window: Duration) -> Result<u64> {
+        let mut state = self.state();
+        let now = Utc::now();
+        let before = state.reservations.len();
+        state
+            .reservations
+            .retain(|_, (reserved_at, _)| *reserved_at + window > now);
+        Ok((before - state.reservations.len()) as u64)
+    }
+}
+
+impl MemoryState {
+    fn insert(&mut self, msg: &Msg, status: DeliveryStatus, attempts: i32) -> (i64, DateTime<Utc>) {
+        let now = Utc::now();
+        self.next_id += 1;
+        if status == DeliveryStatus::Queued {
+            self.queued.insert((now, self.next_id));
+        }
+        self.entries.insert(
+            self.next_id,
+            MemoryEntry {
+                msg: msg.clone(),
+                status,
+                attempts,
+                next_attempt_at: now,
+                last_error: None,
+                results: vec![],
+                updated_at: now,
+            },
+        );
+        (self.next_id, now)
+    }
+
+    /// drop the entries which finished longer than `retention` ago, unless they have been
+    /// requeued since
+    fn evict(&mut self, retention: Duration) {
+        let now = Utc::now();
+        while let Some(&(finished_at, id)) = self.finished.front() {
+            if finished_at + retention > now {
+                break;
+            }
+            self.finished.pop_front();
+            let finished = self.entries.get(&id).is_some_and(|e| {
+                e.updated_at == finished_at
+                    && matches!(
+                        e.status,
+                        DeliveryStatus::Delivered
+                            | DeliveryStatus::Bounced
+                            | DeliveryStatus::Failed
+                    )
+            });
+            if finished {
+                self.entries.remove(&id);
+            }
+        }
+    }
+}
+
+impl MemoryEntry {
+    fn to_status(&self) -> MessageStatus {
+        MessageStatus {
+            message_id: self.msg.message_id().to_string(),
+            status: self.status as i32,
+            attempts: self.attempts.max(0) as u32,
+            last_error: self.last_error.clone().unwrap_or_default(),
+            results: self.results.clone(),
+            updated_at: Some(to_ts(self.updated_at)),
+        }
+    }
+
+    fn to_dead_letter(&self) -> DeadLetter {
+        DeadLetter {
+            message_id: self.msg.message_id().to_string(),
+            request: Some(SendRequest {
+                msg: Some(self.msg.clone()),
+            }),
+            attempts: self.attempts.max(0) as u32,
+            last_error: self.last_error.clone().unwrap_or_default(),
+            failed_at: Some(to_ts(self.updated_at)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::InAppMessage;
+
+    #[tokio::test]
+    async fn finished_entries_should_be_evicted_after_retention() -> Result<()> {
+        let outbox = MemoryOutbox::new(Duration::ZERO);
+        let delivered: Msg = InAppMessage::fake().into();
+        let queued: Msg = InAppMessage::fake().into();
+        outbox.push(&delivered).await?;
+
+        let entries = outbox.claim(10).await?;
+        assert_eq!(entries.len(), 1);
+        outbox
+            .complete(entries[0].id, DeliveryStatus::Delivered, &[])
+            .await?;
+        outbox.push(&queued).await?;
+
+        let ids = [
+            delivered.message_id().to_string(),
+            queued.message_id().to_string(),
+        ];
+        let statuses = outbox.get_status(&ids).await?;
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].message_id, queued.message_id());
+        assert_eq!(outbox.state().entries.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn claim_should_only_take_due_entries() -> Result<()> {
+        let outbox = MemoryOutbox::default();
+        for _ in 0..3 {
+            outbox.push(&InAppMessage::fake().into()).await?;
+        }
+
+        let entries = outbox.claim(2).await?;
+        assert_eq!(entries.len(), 2);
+        outbox
+            .retry(entries[0].id, "timeout", Duration::from_secs(3600))
+            .await?;
+        outbox.fail(entries[1].id, "rejected").await?;
+
+        let entries = outbox.claim(10).await?;
+        assert_eq!(entries.len(), 1);
+        assert!(outbox.claim(10).await?.is_empty());
+
+        Ok(())
+    }
+}
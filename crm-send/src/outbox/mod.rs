@@ -0,0 +1,339 @@
+mod memory;
+mod pg;
+mod retry;
+
+pub use memory::MemoryOutbox;
+pub use pg::PgOutbox;
+
+use crate::{
+    abi::to_ts,
+    config::{OutboxBackend, OutboxConfig, RetryConfig},
+    pb::{
+        send_request::Msg, DeadLetter, DeliveryStatus, MessageStatus, RecipientResult,
+        RecipientStatus, SendResponse,
+    },
+    transport::{is_permanent, retry_after, Delivery, Transports},
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::{broadcast, oneshot, Notify},
+    time::sleep,
+};
+use tonic::async_trait;
+use tracing::{info, warn};
+
+/// a message claimed by a worker for delivery
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub attempts: i32,
+    pub msg: Msg,
+}
+
+/// outcome of reserving a message_id before sending the message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reservation {
+    /// first time the message_id is seen within the window, go ahead and send it
+    Reserved,
+    /// the message has been sent already, this is its original response
+    Sent(SendResponse),
+    /// another request with the same message_id is being accepted right now; it is taken
+    /// over once its lease expires
+    Pending,
+}
+
+const DEDUP_EXPIRE_INTERVAL: Duration = Duration::from_secs(600);
+/// last error of in-app messages handed to their device but not received yet
+const WAITING_FOR_DEVICE: &str = "waiting for the device to receive the message";
+
+/// where accepted messages are kept until they are delivered
+#[async_trait]
+pub trait Outbox: Send + Sync + 'static {
+    /// store the message, returns the time it has been accepted
+    async fn push(&self, msg: &Msg) -> Result<DateTime<Utc>>;
+    /// store the message claimed for its first attempt by the caller, which completes it
+    /// by itself; workers take it over if it is not completed within the lease.
+    /// Returns its id and the time it has been accepted
+    async fn push_claimed(&self, msg: &Msg) -> Result<(i64, DateTime<Utc>)>;
+    /// claim up to `limit` pending messages for delivery
+    async fn claim(&self, limit: usize) -> Result<Vec<OutboxEntry>>;
+    /// mark the message as delivered or bounced
+    async fn complete(
+        &self,
+        id: i64,
+        status: DeliveryStatus,
+        results: &[RecipientResult],
+    ) -> Result<()>;
+    /// schedule another attempt of the message after `delay`
+    async fn retry(&self, id: i64, error: &str, delay: Duration) -> Result<()>;
+    /// give up the message, it becomes a dead letter
+    async fn fail(&self, id: i64, error: &str) -> Result<()>;
+
+    /// dead letters ordered by the time they failed
+    async fn list_dead_letters(&self, limit: usize, offset: usize) -> Result<Vec<DeadLetter>>;
+    async fn get_dead_letter(&self, message_id: &str) -> Result<Option<DeadLetter>>;
+    /// put the dead letters back to the queue with a fresh retry budget
+    async fn requeue_dead_letters(&self, message_ids: &[String]) -> Result<u64>;
+    /// delete the given dead letters, or all of them if `message_ids` is None
+    async fn purge_dead_letters(&self, message_ids: Option<&[String]>) -> Result<u64>;
+
+    /// latest status of the given messages, unknown ones are left out
+    async fn get_status(&self, message_ids: &[String]) -> Result<Vec<MessageStatus>>;
+
+    /// reserve the message_id unless it has been seen within `window`
+    async fn reserve(&self, message_id: &str, window: Duration) -> Result<Reservation>;
+    /// store the response of a reserved message, returned to the duplicates
+    async fn remember(&self, response: &SendResponse) -> Result<()>;
+    /// drop the reservation of a message which could not be accepted, so it could be sent again
+    async fn release(&self, message_id: &str) -> Result<()>;
+    /// forget the reservations older than `window`
+    async fn expire_reservations(&self, window: Duration) -> Result<u64>;
+}
+
+impl OutboxConfig {
+    pub async fn outbox(&self) -> Result<Arc<dyn Outbox>> {
+        let outbox: Arc<dyn Outbox> = match &self.backend {
+            OutboxBackend::Memory => Arc::new(MemoryOutbox::default()),
+            OutboxBackend::Postgres { db_url } => {
+                let pool = PgPool::connect(db_url).await?;
+                Arc::new(PgOutbox::new(pool, self.lease()))
+            }
+        };
+        Ok(outbox)
+    }
+
+    pub fn lease(&self) -> Duration {
+        Duration::from_secs(self.lease_secs)
+    }
+
+    pub fn dedup_window(&self) -> Duration {
+        Duration::from_secs(self.dedup_window_secs)
+    }
+
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+}
+
+/// spawn the workers draining the outbox, `notify` wakes them up when new messages arrive,
+/// every status change is published to `updates`; expired message reservations are cleaned up
+/// in the background as well
+pub fn spawn_workers(
+    config: &OutboxConfig,
+    retry: &RetryConfig,
+    outbox: Arc<dyn Outbox>,
+    transports: Arc<Transports>,
+    notify: Arc<Notify>,
+    updates: broadcast::Sender<MessageStatus>,
+) {
+    for i in 0..config.workers {
+        let outbox = outbox.clone();
+        let transports = transports.clone();
+        let notify = notify.clone();
+        let retry = retry.clone();
+        let updates = updates.clone();
+        let batch_size = config.batch_size;
+        let poll_interval = config.poll_interval();
+
+        tokio::spawn(async move {
+            info!("Outbox worker {} started", i);
+            loop {
+                match outbox.claim(batch_size).await {
+                    Ok(entries) if !entries.is_empty() => {
+                        for entry in entries {
+                            deliver(&outbox, &transports, &retry, &updates, entry).await;
+                        }
+                    }
+                    Ok(_) => {
+                        tokio::select! {
+                            _ = notify.notified() => {}
+                            _ = sleep(poll_interval) => {}
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to claim messages: {:?}", e);
+                        sleep(poll_interval).await;
+                    }
+                }
+            }
+        });
+    }
+
+    let window = config.dedup_window();
+    if !window.is_zero() {
+        tokio::spawn(async move {
+            loop {
+                sleep(window.min(DEDUP_EXPIRE_INTERVAL)).await;
+                match outbox.expire_reservations(window).await {
+                    Ok(n) if n > 0 => info!("Expired {} message reservations", n),
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to expire message reservations: {:?}", e),
+                }
+            }
+        });
+    }
+}
+
+async fn deliver(
+    outbox: &Arc<dyn Outbox>,
+    transports: &Transports,
+    retry: &RetryConfig,
+    updates: &broadcast::Sender<MessageStatus>,
+    entry: OutboxEntry,
+) {
+    let _ = updates.send(entry.status(DeliveryStatus::Sending, None, vec![]));
+
+    let (ret, update) = match transports.deliver(&entry.msg).await {
+        Ok(Delivery::Done(results)) => {
+            let status = DeliveryStatus::from_results(&results);
+            let ret = outbox.complete(entry.id, status, &results).await;
+            (ret, entry.status(status, None, results))
+        }
+        Ok(Delivery::Pending(ack)) => {
+            // the message only lives in memory until its device gets it, keep it queued so
+            // that it is delivered again if it is lost, e.g. on restart
+            let policy = retry.policy(&entry.msg);
+            let attempts = entry.attempts.max(0) as u32;
+            let (ret, status) = if policy.should_retry(attempts) {
+                let delay = policy.backoff(attempts);
+                let ret = outbox.retry(entry.id, WAITING_FOR_DEVICE, delay).await;
+                (ret, DeliveryStatus::Queued)
+            } else {
+                warn!(
+                    "Giving up message {} after {} attempts: {}",
+                    entry.id, attempts, WAITING_FOR_DEVICE
+                );
+                let ret = outbox.fail(entry.id, WAITING_FOR_DEVICE).await;
+                (ret, DeliveryStatus::Failed)
+            };
+            match ret {
+                Ok(_) => {
+                    let error = Some(WAITING_FOR_DEVICE.to_string());
+                    let _ = updates.send(entry.status(status, error, vec![]));
+                    // completed only after the status above, so the ack always wins
+                    wait_for_ack(outbox.clone(), updates.clone(), entry, ack);
+                }
+                Err(e) => warn!("Failed to update message {}: {:?}", entry.id, e),
+            }
+            return;
+        }
+        Err(e) => {
+            let policy = retry.policy(&entry.msg);
+            let attempts = entry.attempts.max(0) as u32;
+            let error = e.to_string();
+            if is_permanent(&e) || !policy.should_retry(attempts) {
+                warn!(
+                    "Giving up message {} after {} attempts: {:?}",
+                    entry.id, attempts, e
+                );
+                let ret = outbox.fail(entry.id, &error).await;
+                (
+                    ret,
+                    entry.status(DeliveryStatus::Failed, Some(error), vec![]),
+                )
+            } else {
+                let delay = retry_after(&e).unwrap_or_else(|| policy.backoff(attempts));
+                warn!(
+                    "Failed to deliver message {}, retry in {:?}: {:?}",
+                    entry.id, delay, e
+                );
+                let ret = outbox.retry(entry.id, &error, delay).await;
+                (
+                    ret,
+                    entry.status(DeliveryStatus::Queued, Some(error), vec![]),
+                )
+            }
+        }
+    };
+    match ret {
+        Ok(_) => {
+            let _ = updates.send(update);
+        }
+        Err(e) => warn!("Failed to update message {}: {:?}", entry.id, e),
+    }
+}
+
+/// complete the message once its device has got it, a lost message is left to be delivered
+/// again
+fn wait_for_ack(
+    outbox: Arc<dyn Outbox>,
+    updates: broadcast::Sender<MessageStatus>,
+    entry: OutboxEntry,
+    ack: oneshot::Receiver<()>,
+) {
+    tokio::spawn(async move {
+        if ack.await.is_err() {
+            return;
+        }
+        match outbox
+            .complete(entry.id, DeliveryStatus::Delivered, &[])
+            .await
+        {
+            Ok(_) => {
+                let _ = updates.send(entry.status(DeliveryStatus::Delivered, None, vec![]));
+            }
+            Err(e) => warn!("Failed to update message {}: {:?}", entry.id, e),
+        }
+    });
+}
+
+impl OutboxEntry {
+    fn status(
+        &self,
+        status: DeliveryStatus,
+        error: Option<String>,
+        results: Vec<RecipientResult>,
+    ) -> MessageStatus {
+        MessageStatus {
+            message_id: self.msg.message_id().to_string(),
+            status: status as i32,
+            attempts: self.attempts.max(0) as u32,
+            last_error: error.unwrap_or_default(),
+            results,
+            updated_at: Some(to_ts(Utc::now())),
+        }
+    }
+}
+
+impl DeliveryStatus {
+    /// a message is bounced when the provider rejected all of its recipients
+    pub fn from_results(results: &[RecipientResult]) -> Self {
+        let rejected = |r: &RecipientResult| r.status() != RecipientStatus::Accepted;
+        if !results.is_empty() && results.iter().all(rejected) {
+            DeliveryStatus::Bounced
+        } else {
+            DeliveryStatus::Delivered
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_should_bounce_only_if_all_recipients_are_rejected() {
+        let result = |status: RecipientStatus| RecipientResult {
+            recipient: "10690000".to_string(),
+            status: status as i32,
+        };
+        assert_eq!(DeliveryStatus::from_results(&[]), DeliveryStatus::Delivered);
+        assert_eq!(
+            DeliveryStatus::from_results(&[
+                result(RecipientStatus::Accepted),
+                result(RecipientStatus::Rejected)
+            ]),
+            DeliveryStatus::Delivered
+        );
+        assert_eq!(
+            DeliveryStatus::from_results(&[
+                result(RecipientStatus::InvalidNumber),
+                result(RecipientStatus::Rejected)
+            ]),
+            DeliveryStatus::Bounced
+        );
+    }
+}
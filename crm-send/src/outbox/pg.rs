@@ -0,0 +1,455 @@
+use super::{Outbox, OutboxEntry, Reservation};
+use crate::{
+    abi::to_ts,
+    pb::{
+        send_request::Msg, DeadLetter, DeliveryStatus, MessageStatus, RecipientResult,
+        RecipientResults, SendRequest, SendResponse,
+    },
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use prost::Message;
+use sqlx::{FromRow, PgPool};
+use std::time::Duration;
+use tonic::async_trait;
+
+/// durable outbox backed by the `outbox` table
+pub struct PgOutbox {
+    pool: PgPool,
+    lease: Duration,
+}
+
+#[derive(Debug, FromRow)]
+struct OutboxRow {
+    id: i64,
+    attempts: i32,
+    payload: Vec<u8>,
+}
+
+#[derive(Debug, FromRow)]
+struct StatusRow {
+    message_id: String,
+    status: String,
+    attempts: i32,
+    last_error: Option<String>,
+    results: Option<Vec<u8>>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+struct DedupRow {
+    response: Option<Vec<u8>>,
+    created_at: DateTime<Utc>,
+    stale: bool,
+}
+
+#[derive(Debug, FromRow)]
+struct AcceptedRow {
+    status: String,
+    results: Option<Vec<u8>>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+struct DeadLetterRow {
+    message_id: String,
+    attempts: i32,
+    payload: Vec<u8>,
+    last_error: Option<String>,
+    updated_at: DateTime<Utc>,
+}
+
+impl PgOutbox {
+    pub fn new(pool: PgPool, lease: Duration) -> Self {
+        Self { pool, lease }
+    }
+}
+
+#[async_trait]
+impl Outbox for PgOutbox {
+    async fn push(&self, msg: &Msg) -> Result<DateTime<Utc>> {
+        let payload = SendRequest {
+            msg: Some(msg.clone()),
+        }
+        .encode_to_vec();
+
+        let (created_at,): (DateTime<Utc>,) = sqlx::query_as(
+            "INSERT INTO outbox(message_id, payload) VALUES ($1, $2) RETURNING created_at",
+        )
+        .bind(msg.message_id())
+        .bind(payload)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(created_at)
+    }
+
+    async fn push_claimed(&self, msg: &Msg) -> Result<(i64, DateTime<Utc>)> {
+        let payload = SendRequest {
+            msg: Some(msg.clone()),
+        }
+        .encode_to_vec();
+
+        let ret: (i64, DateTime<Utc>) = sqlx::query_as(
+            r#"
+            INSERT INTO outbox(message_id, payload, status, attempts, locked_until)
+            VALUES ($1, $2, 'sending', 1, now() + $3)
+            RETURNING id, created_at
+            "#,
+        )
+        .bind(msg.message_id())
+        .bind(payload)
+        .bind(self.lease)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(ret)
+    }
+
+    async fn claim(&self, limit: usize) -> Result<Vec<OutboxEntry>> {
+        // messages left in `sending` after their lease expired belong to a crashed worker
+        let rows: Vec<OutboxRow> = sqlx::query_as(
+            r#"
+            UPDATE outbox
+            SET status = 'sending', attempts = attempts + 1,
+              locked_until = now() + $2, updated_at = now()
+            WHERE id IN (
+              SELECT id FROM outbox
+              WHERE (status = 'queued' AND next_attempt_at <= now())
+                OR (status = 'sending' AND locked_until < now())
+              ORDER BY next_attempt_at, id
+              LIMIT $1
+              FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, attempts, payload
+            "#,
+        )
+        .bind(limit as i64)
+        .bind(self.lease)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let msg = SendRequest::decode(row.payload.as_slice())?
+                    .msg
+                    .ok_or_else(|| anyhow!("empty payload for message {}", row.id))?;
+                Ok(OutboxEntry {
+                    id: row.id,
+                    attempts: row.attempts,
+                    msg,
+                })
+            })
+            .collect()
+    }
+
+    async fn complete(
+        &self,
+        id: i64,
+        status: DeliveryStatus,
+        results: &[RecipientResult],
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE outbox
+            SET status = $2::outbox_status, results = $3, locked_until = NULL, updated_at = now()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(status_name(status))
+        .bind(encode_results(results))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn retry(&self, id: i64, error: &str, delay: Duration) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE outbox
+            SET status = 'queued', locked_until = NULL, last_error = $2,
+              next_attempt_at = now() + $3, updated_at = now()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(error)
+        .bind(delay)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn fail(&self, id: i64, error: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE outbox SET status = 'failed', locked_until = NULL, last_error = $2, updated_at = now() WHERE id = $1",
+        )
+        .bind(id)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_dead_letters(&self, limit: usize, offset: usize) -> Result<Vec<DeadLetter>> {
+        let rows: Vec<DeadLetterRow> = sqlx::query_as(
+            r#"
+            SELECT message_id, attempts, payload, last_error, updated_at
+            FROM outbox
+            WHERE status = 'failed'
+            ORDER BY updated_at, id
+            LIMIT $1 OFFSET $2
+            "#,
+        )
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(DeadLetter::try_from).collect()
+    }
+
+    async fn get_dead_letter(&self, message_id: &str) -> Result<Option<DeadLetter>> {
+        let row: Option<DeadLetterRow> = sqlx::query_as(
+            r#"
+            SELECT message_id, attempts, payload, last_error, updated_at
+            FROM outbox
+            WHERE status = 'failed' AND message_id = $1
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(message_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(DeadLetter::try_from).transpose()
+    }
+
+    async fn requeue_dead_letters(&self, message_ids: &[String]) -> Result<u64> {
+        let ret = sqlx::query(
+            r#"
+            UPDATE outbox
+            SET status = 'queued', attempts = 0, next_attempt_at = now(), updated_at = now()
+            WHERE status = 'failed' AND message_id = ANY($1)
+            "#,
+        )
+        .bind(message_ids)
+        .execute(&self.pool)
+        .await?;
+        Ok(ret.rows_affected())
+    }
+
+    async fn purge_dead_letters(&self, message_ids: Option<&[String]>) -> Result<u64> {
+        let ret = match message_ids {
+            Some(ids) => {
+                sqlx::query("DELETE FROM outbox WHERE status = 'failed' AND message_id = ANY($1)")
+                    .bind(ids)
+                    .execute(&self.pool)
+                    .await?
+            }
+            None => {
+                sqlx::query("DELETE FROM outbox WHERE status = 'failed'")
+                    .execute(&self.pool)
+                    .await?
+            }
+        };
+        Ok(ret.rows_affected())
+    }
+
+    async fn get_status(&self, message_ids: &[String]) -> Result<Vec<MessageStatus>> {
+        let rows: Vec<StatusRow> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT ON (message_id)
+              message_id, status::text, attempts, last_error, results, updated_at
+            FROM outbox
+            WHERE message_id = ANY($1)
+            ORDER BY message_id, id DESC
+            "#,
+        )
+        .bind(message_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(MessageStatus::try_from).collect()
+    }
+
+    async fn reserve(&self, message_id: &str, window: Duration) -> Result<Reservation> {
+        // the primary key makes sure only one of the concurrent duplicates wins
+        let reserved: Option<(String,)> = sqlx::query_as(
+            r#"
+            INSERT INTO dedup(message_id) VALUES ($1)
+            ON CONFLICT (message_id) DO UPDATE SET response = NULL, created_at = now()
+            WHERE dedup.created_at < now() - $2
+            RETURNING message_id
+            "#,
+        )
+        .bind(message_id)
+        .bind(window)
+        .fetch_optional(&self.pool)
+        .await?;
+        if reserved.is_some() {
+            return Ok(Reservation::Reserved);
+        }
+
+        let row: Option<DedupRow> = sqlx::query_as(
+            "SELECT response, created_at, created_at < now() - $2 AS stale FROM dedup WHERE message_id = $1",
+        )
+        .bind(message_id)
+        .bind(self.lease)
+        .fetch_optional(&self.pool)
+        .await?;
+        let row = match row {
+            Some(row) => row,
+            // expired and deleted in between, try again
+            None => return self.reserve(message_id, window).await,
+        };
+        match row.response {
+            Some(response) => Ok(Reservation::Sent(SendResponse::decode(
+                response.as_slice(),
+            )?)),
+            None if row.stale => self.take_over(message_id, row.created_at).await,
+            None => Ok(Reservation::Pending),
+        }
+    }
+
+    async fn remember(&self, response: &SendResponse) -> Result<()> {
+        sqlx::query("UPDATE dedup SET response = $2 WHERE message_id = $1")
+            .bind(&response.message_id)
+            .bind(response.encode_to_vec())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn release(&self, message_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM dedup WHERE message_id = $1")
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn expire_reservations(&self, window: Duration) -> Result<u64> {
+        let ret = sqlx::query("DELETE FROM dedup WHERE created_at < now() - $1")
+            .bind(window)
+            .execute(&self.pool)
+            .await?;
+        Ok(ret.rows_affected())
+    }
+}
+
+impl PgOutbox {
+    /// the request holding the reservation didn't store its response within the lease, e.g.
+    /// it crashed: answer with the message it left in the outbox, or reserve it again
+    async fn take_over(&self, message_id: &str, reserved_at: DateTime<Utc>) -> Result<Reservation> {
+        let row: Option<AcceptedRow> = sqlx::query_as(
+            r#"
+                SELECT status::text, results, created_at FROM outbox
+                WHERE message_id = $1 AND created_at >= $2
+                ORDER BY id DESC
+                LIMIT 1
+                "#,
+        )
+        .bind(message_id)
+        .bind(reserved_at)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(row) = row {
+            let response = SendResponse {
+                message_id: message_id.to_string(),
+                timestamp: Some(to_ts(row.created_at)),
+                results: decode_results(row.results)?,
+                status: parse_status(&row.status)? as i32,
+            };
+            self.remember(&response).await?;
+            return Ok(Reservation::Sent(response));
+        }
+
+        // only one of the concurrent duplicates gets the stale reservation
+        let ret = sqlx::query(
+            r#"
+                UPDATE dedup SET created_at = now()
+                WHERE message_id = $1 AND response IS NULL AND created_at = $2
+                "#,
+        )
+        .bind(message_id)
+        .bind(reserved_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(match ret.rows_affected() {
+            0 => Reservation::Pending,
+            _ => Reservation::Reserved,
+        })
+    }
+}
+
+impl TryFrom<StatusRow> for MessageStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(row: StatusRow) -> Result<Self> {
+        Ok(MessageStatus {
+            message_id: row.message_id,
+            status: parse_status(&row.status)? as i32,
+            attempts: row.attempts.max(0) as u32,
+            last_error: row.last_error.unwrap_or_default(),
+            results: decode_results(row.results)?,
+            updated_at: Some(to_ts(row.updated_at)),
+        })
+    }
+}
+
+impl TryFrom<DeadLetterRow> for DeadLetter {
+    type Error = anyhow::Error;
+
+    fn try_from(row: DeadLetterRow) -> Result<Self> {
+        Ok(DeadLetter {
+            message_id: row.message_id,
+            request: Some(SendRequest::decode(row.payload.as_slice())?),
+            attempts: row.attempts.max(0) as u32,
+            last_error: row.last_error.unwrap_or_default(),
+            failed_at: Some(to_ts(row.updated_at)),
+        })
+    }
+}
+
+/// name of the status in the `outbox_status` enum
+fn status_name(status: DeliveryStatus) -> &'static str {
+    match status {
+        DeliveryStatus::Unspecified | DeliveryStatus::Queued => "queued",
+        DeliveryStatus::Sending => "sending",
+        DeliveryStatus::Delivered => "delivered",
+        DeliveryStatus::Failed => "failed",
+        DeliveryStatus::Bounced => "bounced",
+    }
+}
+
+fn parse_status(status: &str) -> Result<DeliveryStatus> {
+    match status {
+        "queued" => Ok(DeliveryStatus::Queued),
+        "sending" => Ok(DeliveryStatus::Sending),
+        "delivered" => Ok(DeliveryStatus::Delivered),
+        "failed" => Ok(DeliveryStatus::Failed),
+        "bounced" => Ok(DeliveryStatus::Bounced),
+        v => Err(anyhow!("unknown outbox status {}", v)),
+    }
+}
+
+fn decode_results(results: Option<Vec<u8>>) -> Result<Vec<RecipientResult>> {
+    match results {
+        Some(results) => Ok(RecipientResults::decode(results.as_slice())?.results),
+        None => Ok(vec![]),
+    }
+}
+
+fn encode_results(results: &[RecipientResult]) -> Option<Vec<u8>> {
+    if results.is_empty() {
+        return None;
+    }
+    let results = RecipientResults {
+        results: results.to_vec(),
+    };
+    Some(results.encode_to_vec())
+}
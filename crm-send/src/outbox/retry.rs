@@ -0,0 +1,73 @@
+use crate::{
+    config::{RetryConfig, RetryPolicy},
+    pb::send_request::Msg,
+};
+use rand::Rng;
+use std::time::Duration;
+
+impl RetryConfig {
+    pub fn policy(&self, msg: &Msg) -> &RetryPolicy {
+        match msg {
+            Msg::Email(_) => &self.email,
+            Msg::Sms(_) => &self.sms,
+            Msg::InApp(_) => &self.in_app,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// whether another attempt is allowed after `attempts` failed ones
+    pub fn should_retry(&self, attempts: u32) -> bool {
+        attempts < self.max_attempts
+    }
+
+    /// delay before the next attempt, after `attempts` failed ones
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let exp = attempts.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = (self.initial_backoff_ms as f64 * self.multiplier.powi(exp))
+            .min(self.max_backoff_ms as f64);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)
+        } else {
+            1.0
+        };
+        Duration::from_millis((delay * factor) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_should_grow_exponentially_up_to_max() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+            multiplier: 2.0,
+            jitter: 0.0,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5), Duration::from_millis(1000));
+        assert!(policy.should_retry(4));
+        assert!(!policy.should_retry(5));
+    }
+
+    #[test]
+    fn backoff_should_apply_jitter() {
+        let policy = RetryPolicy {
+            initial_backoff_ms: 1000,
+            jitter: 0.5,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let delay = policy.backoff(1);
+            assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1500));
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use super::{EmailTransport, SmsProvider};
+use crate::pb::{EmailMessage, RecipientResult, RecipientStatus, SmsMessage};
+use anyhow::Result;
+use std::time::Duration;
+use tokio::time::sleep;
+use tonic::async_trait;
+use tracing::info;
+
+/// transport that only logs the message, used for local development and tests
+pub struct DummyTransport;
+
+#[async_trait]
+impl EmailTransport for DummyTransport {
+    async fn send(&self, email: &EmailMessage) -> Result<()> {
+        info!("Sending email: {:?}", email);
+        sleep(Duration::from_millis(300)).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SmsProvider for DummyTransport {
+    async fn send(&self, sms: &SmsMessage) -> Result<Vec<RecipientResult>> {
+        info!("Sending sms: {:?}", sms);
+        let results = sms
+            .recipients
+            .iter()
+            .map(|recipient| RecipientResult {
+                recipient: recipient.clone(),
+                status: RecipientStatus::Accepted as _,
+            })
+            .collect();
+        Ok(results)
+    }
+}
@@ -0,0 +1,332 @@
+use crate::pb::InAppMessage;
+use futures::{Stream, StreamExt};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+use tracing::{info, warn};
+
+const CHANNEL_SIZE: usize = 1024;
+
+/// fired once the subscriber has got the message, dropped if the message is lost
+pub type Ack = oneshot::Sender<()>;
+
+type DeviceSender = mpsc::Sender<(InAppMessage, Ack)>;
+
+/// routes in-app messages to subscribed devices, buffering them while a device is offline
+/// or too slow to keep up. Buffered messages are lost on restart, so every message comes
+/// with an ack the sender waits on before considering it delivered
+pub struct InAppHub {
+    buffer_size: usize,
+    idle_timeout: Duration,
+    devices: Arc<Mutex<Devices>>,
+    dropped: AtomicU64,
+}
+
+struct Devices {
+    devices: HashMap<String, Device>,
+    last_sweep: Instant,
+}
+
+struct Device {
+    /// the subscription of the device, if it is online
+    tx: Option<DeviceSender>,
+    /// messages waiting for the device to come online or to drain its channel
+    buffered: VecDeque<(InAppMessage, Ack)>,
+    /// whether a task is moving buffered messages into the channel
+    draining: bool,
+    last_seen: Instant,
+}
+
+impl InAppHub {
+    pub fn new(buffer_size: usize, idle_timeout: Duration) -> Self {
+        Self {
+            buffer_size,
+            idle_timeout,
+            devices: Arc::new(Mutex::new(Devices {
+                devices: HashMap::new(),
+                last_sweep: Instant::now(),
+            })),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// number of messages dropped because a device buffer was full or the device went idle
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// subscribe a device, replacing any previous subscription of the same device; messages
+    /// are acked as the stream yields them
+    pub fn subscribe(
+        &self,
+        device_id: &str,
+    ) -> impl Stream<Item = Result<InAppMessage, Status>> + Send + 'static {
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE.max(self.buffer_size));
+        let mut devices = self.devices.lock().unwrap();
+        self.sweep(&mut devices);
+
+        let device = devices
+            .devices
+            .entry(device_id.to_string())
+            .or_insert_with(Device::new);
+        if !device.buffered.is_empty() {
+            info!(
+                "Flushing {} buffered messages to device {}",
+                device.buffered.len(),
+                device_id
+            );
+        }
+        for msg in device.buffered.drain(..) {
+            // capacity is at least buffer_size, so this never fails for a fresh channel
+            let _ = tx.try_send(msg);
+        }
+        device.tx = Some(tx);
+        device.draining = false;
+        device.last_seen = Instant::now();
+
+        ReceiverStream::new(rx)
+            .map(|(msg, ack)| {
+                let _ = ack.send(());
+                msg
+            })
+            .map(Ok)
+    }
+
+    /// hand the message to its device without waiting, or buffer it if the device is
+    /// offline or its channel is full. A message redelivered while still buffered replaces
+    /// the buffered one, whose ack is dropped
+    pub fn deliver(&self, msg: InAppMessage, ack: Ack) {
+        let mut devices = self.devices.lock().unwrap();
+        self.sweep(&mut devices);
+
+        let device_id = msg.device_id.clone();
+        let device = devices
+            .devices
+            .entry(device_id.clone())
+            .or_insert_with(Device::new);
+        device.last_seen = Instant::now();
+
+        if let Some(buffered) = device
+            .buffered
+            .iter_mut()
+            .find(|(m, _)| m.message_id == msg.message_id)
+        {
+            *buffered = (msg, ack);
+            return;
+        }
+
+        // keep the order of messages already waiting in the buffer
+        let msg = match device.tx.clone() {
+            Some(tx) if device.buffered.is_empty() => match tx.try_send((msg, ack)) {
+                Ok(_) => return,
+                Err(TrySendError::Full(msg)) => {
+                    self.start_draining(device, device_id.clone(), tx);
+                    msg
+                }
+                Err(TrySendError::Closed(msg)) => {
+                    device.tx = None;
+                    msg
+                }
+            },
+            _ => (msg, ack),
+        };
+
+        if device.buffered.len() >= self.buffer_size {
+            device.buffered.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "Buffer of device {} is full, dropped its oldest message",
+                device_id
+            );
+        }
+        device.buffered.push_back(msg);
+    }
+
+    /// move buffered messages into the channel of the device as its consumer catches up
+    fn start_draining(&self, device: &mut Device, device_id: String, tx: DeviceSender) {
+        if device.draining {
+            return;
+        }
+        device.draining = true;
+
+        let devices = self.devices.clone();
+        tokio::spawn(async move {
+            while let Ok(permit) = tx.reserve().await {
+                let mut devices = devices.lock().unwrap();
+                let Some(device) = devices.devices.get_mut(&device_id) else {
+                    return;
+                };
+                // the device has resubscribed, the new subscription took the buffer over
+                if !device.tx.as_ref().is_some_and(|t| t.same_channel(&tx)) {
+                    return;
+                }
+                match device.buffered.pop_front() {
+                    Some(msg) => permit.send(msg),
+                    None => {
+                        device.draining = false;
+                        return;
+                    }
+                }
+            }
+
+            // the device has disconnected, keep the rest buffered until it comes back
+            let mut devices = devices.lock().unwrap();
+            if let Some(device) = devices.devices.get_mut(&device_id) {
+                if device.tx.as_ref().is_some_and(|t| t.same_channel(&tx)) {
+                    device.tx = None;
+                    device.draining = false;
+                }
+            }
+        });
+    }
+
+    /// forget devices that have been offline for longer than the idle timeout, at most
+    /// once per idle timeout
+    fn sweep(&self, devices: &mut Devices) {
+        if devices.last_sweep.elapsed() < self.idle_timeout {
+            return;
+        }
+        devices.last_sweep = Instant::now();
+
+        let idle_timeout = self.idle_timeout;
+        let mut dropped = 0;
+        devices.devices.retain(|device_id, device| {
+            let online = device.tx.as_ref().is_some_and(|tx| !tx.is_closed());
+            if online || device.last_seen.elapsed() < idle_timeout {
+                return true;
+            }
+            if !device.buffered.is_empty() {
+                warn!(
+                    "Device {} is idle, dropped its {} buffered messages",
+                    device_id,
+                    device.buffered.len()
+                );
+            }
+            dropped += device.buffered.len() as u64;
+            false
+        });
+        self.dropped.fetch_add(dropped, Ordering::Relaxed);
+    }
+}
+
+impl Device {
+    fn new() -> Self {
+        Self {
+            tx: None,
+            buffered: VecDeque::new(),
+            draining: false,
+            last_seen: Instant::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    const IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+
+    fn msg(device_id: &str, title: &str) -> InAppMessage {
+        InAppMessage {
+            message_id: title.to_string(),
+            device_id: device_id.to_string(),
+            title: title.to_string(),
+            body: "Hello, world!".to_string(),
+        }
+    }
+
+    fn deliver(hub: &InAppHub, msg: InAppMessage) -> oneshot::Receiver<()> {
+        let (ack, rx) = oneshot::channel();
+        hub.deliver(msg, ack);
+        rx
+    }
+
+    #[tokio::test]
+    async fn deliver_should_reach_online_device() {
+        let hub = InAppHub::new(10, IDLE_TIMEOUT);
+        let mut rx = Box::pin(hub.subscribe("d1"));
+        let mut ack = deliver(&hub, msg("d1", "hello"));
+        assert!(ack.try_recv().is_err());
+        let ret = rx.next().await.unwrap().unwrap();
+        assert_eq!(ret.title, "hello");
+        assert!(ack.await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn offline_messages_should_be_buffered_until_reconnect() {
+        let hub = InAppHub::new(2, IDLE_TIMEOUT);
+        let rx = hub.subscribe("d1");
+        drop(rx);
+
+        let acks: Vec<_> = ["m1", "m2", "m3"]
+            .into_iter()
+            .map(|title| deliver(&hub, msg("d1", title)))
+            .collect();
+
+        let mut rx = Box::pin(hub.subscribe("d1"));
+        assert_eq!(rx.next().await.unwrap().unwrap().title, "m2");
+        assert_eq!(rx.next().await.unwrap().unwrap().title, "m3");
+        assert!(rx.next().now_or_never().is_none());
+        assert_eq!(hub.dropped(), 1);
+
+        // the dropped message is never acked, so its sender knows it is lost
+        let acks: Vec<_> = futures::future::join_all(acks).await;
+        assert!(acks[0].is_err());
+        assert!(acks[1].is_ok() && acks[2].is_ok());
+    }
+
+    #[tokio::test]
+    async fn deliver_to_full_channel_should_not_block_and_keep_order() {
+        let hub = InAppHub::new(10, IDLE_TIMEOUT);
+        let mut rx = Box::pin(hub.subscribe("d1"));
+
+        let total = CHANNEL_SIZE + 5;
+        for i in 0..total {
+            deliver(&hub, msg("d1", &i.to_string()));
+        }
+
+        for i in 0..total {
+            assert_eq!(rx.next().await.unwrap().unwrap().title, i.to_string());
+        }
+        assert_eq!(hub.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn idle_devices_should_be_removed() {
+        let hub = InAppHub::new(10, Duration::from_millis(10));
+        let ack = deliver(&hub, msg("d1", "m1"));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        deliver(&hub, msg("d2", "m2"));
+
+        assert!(!hub.devices.lock().unwrap().devices.contains_key("d1"));
+        assert_eq!(hub.dropped(), 1);
+        assert!(ack.await.is_err());
+        let mut rx = Box::pin(hub.subscribe("d1"));
+        assert!(rx.next().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn redelivered_message_should_replace_the_buffered_one() {
+        let hub = InAppHub::new(10, IDLE_TIMEOUT);
+        let first = deliver(&hub, msg("d1", "m1"));
+        let second = deliver(&hub, msg("d1", "m1"));
+        assert!(first.await.is_err());
+
+        let mut rx = Box::pin(hub.subscribe("d1"));
+        assert_eq!(rx.next().await.unwrap().unwrap().title, "m1");
+        assert!(rx.next().now_or_never().is_none());
+        assert!(second.await.is_ok());
+    }
+}
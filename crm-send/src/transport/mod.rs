@@ -0,0 +1,126 @@
+mod dummy;
+mod in_app;
+mod smtp;
+mod webhook;
+
+pub use dummy::DummyTransport;
+pub use in_app::InAppHub;
+pub use smtp::SmtpTransport;
+pub use webhook::WebhookProvider;
+
+use crate::{
+    config::{EmailConfig, SmsConfig},
+    pb::{send_request::Msg, EmailMessage, RecipientResult, SmsMessage},
+    AppConfig,
+};
+use anyhow::Result;
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::sync::oneshot;
+use tonic::async_trait;
+
+/// an error that won't go away by retrying, e.g. the recipient doesn't exist
+#[derive(Debug)]
+pub struct Permanent(pub anyhow::Error);
+
+impl fmt::Display for Permanent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "permanent error: {}", self.0)
+    }
+}
+
+impl std::error::Error for Permanent {}
+
+pub fn is_permanent(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<Permanent>().is_some()
+}
+
+/// an error the backend asks to retry no sooner than after the delay, e.g. when rate limited
+#[derive(Debug)]
+pub struct RetryAfter(pub Duration, pub anyhow::Error);
+
+impl fmt::Display for RetryAfter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (retry after {:?})", self.1, self.0)
+    }
+}
+
+impl std::error::Error for RetryAfter {}
+
+pub fn retry_after(e: &anyhow::Error) -> Option<Duration> {
+    e.downcast_ref::<RetryAfter>().map(|e| e.0)
+}
+
+/// how far a transport got with a message
+#[derive(Debug)]
+pub enum Delivery {
+    /// the backend has taken the message, with the per-recipient results if it reports them
+    Done(Vec<RecipientResult>),
+    /// the message waits in memory for its device, the receiver fires once the device has
+    /// got it and fails if the message is lost
+    Pending(oneshot::Receiver<()>),
+}
+
+/// a backend that actually delivers email messages
+#[async_trait]
+pub trait EmailTransport: Send + Sync + 'static {
+    async fn send(&self, email: &EmailMessage) -> Result<()>;
+}
+
+/// a provider that delivers sms messages and reports the result of every recipient
+#[async_trait]
+pub trait SmsProvider: Send + Sync + 'static {
+    async fn send(&self, sms: &SmsMessage) -> Result<Vec<RecipientResult>>;
+}
+
+/// all the backends a message could be delivered through
+pub struct Transports {
+    pub email: Box<dyn EmailTransport>,
+    pub sms: Box<dyn SmsProvider>,
+    pub in_app: Arc<InAppHub>,
+}
+
+impl Transports {
+    pub fn try_new(config: &AppConfig) -> Result<Self> {
+        Ok(Self {
+            email: config.email.transport()?,
+            sms: config.sms.provider()?,
+            in_app: Arc::new(InAppHub::new(
+                config.in_app.buffer_size,
+                Duration::from_secs(config.in_app.idle_timeout_secs),
+            )),
+        })
+    }
+
+    /// deliver the message through the transport matching its type
+    pub async fn deliver(&self, msg: &Msg) -> Result<Delivery> {
+        match msg {
+            Msg::Email(email) => self.email.send(email).await.map(|_| Delivery::Done(vec![])),
+            Msg::Sms(sms) => self.sms.send(sms).await.map(Delivery::Done),
+            Msg::InApp(in_app) => {
+                let (ack, rx) = oneshot::channel();
+                self.in_app.deliver(in_app.clone(), ack);
+                Ok(Delivery::Pending(rx))
+            }
+        }
+    }
+}
+
+impl EmailConfig {
+    pub fn transport(&self) -> Result<Box<dyn EmailTransport>> {
+        let transport: Box<dyn EmailTransport> = match self {
+            EmailConfig::Dummy => Box::new(DummyTransport),
+            EmailConfig::Smtp(config) => Box::new(SmtpTransport::try_new(config)?),
+        };
+        Ok(transport)
+    }
+}
+
+impl SmsConfig {
+    pub fn provider(&self) -> Result<Box<dyn SmsProvider>> {
+        let provider: Box<dyn SmsProvider> = match self {
+            SmsConfig::Dummy => Box::new(DummyTransport),
+            SmsConfig::Webhook(config) => Box::new(WebhookProvider::try_new(config)?),
+        };
+        Ok(provider)
+    }
+}
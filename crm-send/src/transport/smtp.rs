@@ -0,0 +1,71 @@
+use super::{EmailTransport, Permanent};
+use crate::{config::SmtpConfig, pb::EmailMessage};
+use anyhow::Result;
+use lettre::{
+    message::{header::ContentType, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use tonic::async_trait;
+
+pub struct SmtpTransport(AsyncSmtpTransport<Tokio1Executor>);
+
+impl SmtpTransport {
+    pub fn try_new(config: &SmtpConfig) -> Result<Self> {
+        let mut builder = if config.starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+        };
+        builder = builder.port(config.port);
+
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self(builder.build()))
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, email: &EmailMessage) -> Result<()> {
+        // a message we can't even build will never be delivered
+        let message = email.to_mime().map_err(Permanent)?;
+        self.0.send(message).await.map_err(|e| {
+            if e.is_permanent() {
+                Permanent(e.into()).into()
+            } else {
+                anyhow::Error::from(e)
+            }
+        })?;
+        Ok(())
+    }
+}
+
+impl EmailMessage {
+    /// build the MIME message to be delivered over SMTP
+    pub fn to_mime(&self) -> Result<Message> {
+        let mut builder = Message::builder()
+            .message_id(Some(format!("<{}@crm-send>", self.message_id)))
+            .from(self.sender.parse()?)
+            .subject(&self.subject);
+        // a message batched for many users mustn't disclose them to each other
+        let batched = self.recipients.len() > 1;
+        for recipient in &self.recipients {
+            builder = if batched {
+                builder.bcc(recipient.parse()?)
+            } else {
+                builder.to(recipient.parse()?)
+            };
+        }
+
+        if self.html_body.is_empty() {
+            return Ok(builder
+                .header(ContentType::TEXT_PLAIN)
+                .body(self.body.clone())?);
+        }
+        let body = MultiPart::alternative_plain_html(self.body.clone(), self.html_body.clone());
+        Ok(builder.multipart(body)?)
+    }
+}
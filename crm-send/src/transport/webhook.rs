@@ -0,0 +1,159 @@
+use super::{Permanent, RetryAfter, SmsProvider};
+use crate::{
+    config::WebhookConfig,
+    pb::{RecipientResult, RecipientStatus, SmsMessage},
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use reqwest::{header::RETRY_AFTER, Client, StatusCode};
+use serde_json::{json, Map, Value};
+use std::{collections::HashMap, time::Duration};
+use tonic::async_trait;
+
+/// sms provider reached through a json http webhook
+pub struct WebhookProvider {
+    client: Client,
+    config: WebhookConfig,
+}
+
+impl WebhookProvider {
+    pub fn try_new(config: &WebhookConfig) -> Result<Self> {
+        let client = Client::builder()
+            .connect_timeout(Duration::from_millis(config.timeouts.connect_ms))
+            .timeout(Duration::from_millis(config.timeouts.request_ms))
+            .build()?;
+        Ok(Self {
+            client,
+            config: config.clone(),
+        })
+    }
+
+    fn payload(&self, sms: &SmsMessage) -> Value {
+        let fields = &self.config.payload;
+        let mut payload = Map::new();
+        payload.insert(fields.message_id.clone(), json!(sms.message_id));
+        payload.insert(fields.sender.clone(), json!(sms.sender));
+        payload.insert(fields.recipients.clone(), json!(sms.recipients));
+        payload.insert(fields.body.clone(), json!(sms.body));
+        Value::Object(payload)
+    }
+
+    fn results(&self, sms: &SmsMessage, response: &Value) -> Result<Vec<RecipientResult>> {
+        let mapping = &self.config.response;
+        // the provider may have sent the message already, don't retry it
+        let items = response
+            .get(&mapping.results)
+            .and_then(Value::as_array)
+            .ok_or_else(|| {
+                Permanent(anyhow!(
+                    "missing `{}` in provider response",
+                    mapping.results
+                ))
+            })?;
+
+        let statuses: HashMap<&str, RecipientStatus> = items
+            .iter()
+            .filter_map(|item| {
+                let recipient = item.get(&mapping.recipient)?.as_str()?;
+                let status = item.get(&mapping.status)?.as_str()?;
+                Some((recipient, self.status(status)))
+            })
+            .collect();
+
+        // recipients the provider doesn't report on are treated as rejected
+        let results = sms
+            .recipients
+            .iter()
+            .map(|recipient| RecipientResult {
+                recipient: recipient.clone(),
+                status: statuses
+                    .get(recipient.as_str())
+                    .copied()
+                    .unwrap_or(RecipientStatus::Rejected) as _,
+            })
+            .collect();
+        Ok(results)
+    }
+
+    fn status(&self, status: &str) -> RecipientStatus {
+        let mapping = &self.config.response;
+        if mapping.accepted.iter().any(|v| v == status) {
+            RecipientStatus::Accepted
+        } else if mapping.invalid_number.iter().any(|v| v == status) {
+            RecipientStatus::InvalidNumber
+        } else {
+            RecipientStatus::Rejected
+        }
+    }
+}
+
+#[async_trait]
+impl SmsProvider for WebhookProvider {
+    async fn send(&self, sms: &SmsMessage) -> Result<Vec<RecipientResult>> {
+        let mut req = self.client.post(&self.config.url).json(&self.payload(sms));
+        if let (Some(name), Some(value)) = (&self.config.auth_header, &self.config.auth_value) {
+            req = req.header(name, value);
+        }
+
+        // timeouts and connection failures are retried
+        let response = req.send().await?;
+        let status = response.status();
+        if matches!(
+            status,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS
+        ) {
+            let delay = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| parse_retry_after(v, Utc::now()));
+            let e = anyhow!("provider returned {}", status);
+            return Err(match delay {
+                Some(delay) => RetryAfter(delay, e).into(),
+                None => e,
+            });
+        }
+        if status.is_client_error() {
+            // the provider refuses the request itself, sending it again won't help
+            let body = response.text().await.unwrap_or_default();
+            return Err(Permanent(anyhow!("provider returned {}: {}", status, body)).into());
+        }
+
+        let response: Value = response.error_for_status()?.json().await?;
+        self.results(sms, &response)
+    }
+}
+
+/// `Retry-After` is either a number of seconds or an http date
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_after_should_accept_seconds_and_dates() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:29:30 GMT", now),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
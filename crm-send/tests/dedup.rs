@@ -0,0 +1,128 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use crm_send::{
+    pb::{DeliveryStatus, InAppMessage, SendRequest, SendResponse, SmsMessage},
+    test_utils::get_test_pool,
+    AppConfig, NotificationService, Outbox, PgOutbox,
+};
+use futures::StreamExt;
+use tokio::time::sleep;
+use tonic::Status;
+
+#[tokio::test]
+async fn repeated_message_should_return_original_response() -> Result<()> {
+    let config = AppConfig::load_for_test()?;
+    let service = NotificationService::new(config).await?;
+
+    let msg = SmsMessage::fake();
+    let first = send(&service, msg.clone().into()).await?;
+    sleep(Duration::from_millis(10)).await;
+    let second = send(&service, msg.into()).await?;
+    assert_eq!(first, second);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn dedup_should_be_disabled_with_zero_window() -> Result<()> {
+    let mut config = AppConfig::load_for_test()?;
+    config.outbox.dedup_window_secs = 0;
+    let service = NotificationService::new(config).await?;
+
+    let msg = SmsMessage::fake();
+    let first = send(&service, msg.clone().into()).await?;
+    sleep(Duration::from_millis(10)).await;
+    let second = send(&service, msg.into()).await?;
+    assert_ne!(first.timestamp, second.timestamp);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn dedup_should_survive_restart() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let mut config = AppConfig::load_for_test()?;
+    config.outbox.workers = 0;
+
+    let msg = InAppMessage::fake();
+    let outbox = Arc::new(PgOutbox::new(pool.clone(), config.outbox.lease()));
+    let service = NotificationService::with_outbox(config.clone(), outbox)?;
+    let first = send(&service, msg.clone().into()).await?;
+    drop(service);
+
+    let outbox = Arc::new(PgOutbox::new(pool.clone(), config.outbox.lease()));
+    let service = NotificationService::with_outbox(config, outbox)?;
+    let second = send(&service, msg.clone().into()).await?;
+    assert_eq!(first, second);
+
+    let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM outbox WHERE message_id = $1")
+        .bind(&msg.message_id)
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(count, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn stale_reservation_should_be_taken_over_after_restart() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let mut config = AppConfig::load_for_test()?;
+    config.outbox.workers = 0;
+    let outbox = Arc::new(PgOutbox::new(pool.clone(), Duration::ZERO));
+
+    // crashed right after reserving, before the message got into the outbox
+    let msg = InAppMessage::fake();
+    outbox
+        .reserve(&msg.message_id, config.outbox.dedup_window())
+        .await?;
+
+    let service = NotificationService::with_outbox(config, outbox)?;
+    let ret = send(&service, msg.clone().into()).await?;
+    assert_eq!(ret.status(), DeliveryStatus::Queued);
+
+    let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM outbox WHERE message_id = $1")
+        .bind(&msg.message_id)
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(count, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn stale_reservation_should_return_the_accepted_message() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let mut config = AppConfig::load_for_test()?;
+    config.outbox.workers = 0;
+    let outbox = Arc::new(PgOutbox::new(pool.clone(), Duration::ZERO));
+
+    // crashed after the message got into the outbox, before its response was stored
+    let msg = InAppMessage::fake();
+    outbox
+        .reserve(&msg.message_id, config.outbox.dedup_window())
+        .await?;
+    let accepted_at = outbox.push(&msg.clone().into()).await?;
+
+    let service = NotificationService::with_outbox(config, outbox)?;
+    let ret = send(&service, msg.clone().into()).await?;
+    let ts = ret.timestamp.clone().unwrap();
+    assert_eq!(ts.seconds, accepted_at.timestamp());
+    assert_eq!(ts.nanos as u32, accepted_at.timestamp_subsec_nanos());
+    assert_eq!(ret.status(), DeliveryStatus::Queued);
+
+    let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM outbox WHERE message_id = $1")
+        .bind(&msg.message_id)
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(count, 1);
+
+    Ok(())
+}
+
+async fn send(service: &NotificationService, req: SendRequest) -> Result<SendResponse, Status> {
+    let stream = tokio_stream::iter(vec![Ok(req)]);
+    let mut ret = service.send(stream).await?.into_inner();
+    ret.next().await.expect("response stream closed")
+}
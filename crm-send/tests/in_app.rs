@@ -0,0 +1,80 @@
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::Result;
+use crm_send::{
+    pb::{notification_client::NotificationClient, InAppMessage, SendRequest, SubscribeRequest},
+    AppConfig, NotificationService,
+};
+use futures::StreamExt;
+use tokio::time::{sleep, timeout};
+use tonic::transport::Server;
+
+const PORT_BASE: u32 = 60100;
+
+#[tokio::test]
+async fn subscribed_device_should_receive_in_app_messages() -> Result<()> {
+    let addr = start_server(PORT_BASE).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let msg = InAppMessage::fake();
+    let mut stream = client
+        .subscribe(SubscribeRequest {
+            device_id: msg.device_id.clone(),
+        })
+        .await?
+        .into_inner();
+
+    let reqs = tokio_stream::iter(vec![SendRequest::from(msg.clone())]);
+    let ret: Vec<_> = client.send(reqs).await?.into_inner().collect().await;
+    assert_eq!(ret.len(), 1);
+
+    let received = timeout(Duration::from_secs(5), stream.next())
+        .await?
+        .expect("subscription closed")?;
+    assert_eq!(received, msg);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn offline_device_should_receive_buffered_messages_on_subscribe() -> Result<()> {
+    let addr = start_server(PORT_BASE + 1).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let msg = InAppMessage::fake();
+    let reqs = tokio_stream::iter(vec![SendRequest::from(msg.clone())]);
+    let ret: Vec<_> = client.send(reqs).await?.into_inner().collect().await;
+    assert_eq!(ret.len(), 1);
+    // give the dispatcher a chance to buffer the message
+    sleep(Duration::from_millis(100)).await;
+
+    let mut stream = client
+        .subscribe(SubscribeRequest {
+            device_id: msg.device_id.clone(),
+        })
+        .await?
+        .into_inner();
+    let received = timeout(Duration::from_secs(5), stream.next())
+        .await?
+        .expect("subscription closed")?;
+    assert_eq!(received, msg);
+
+    Ok(())
+}
+
+async fn start_server(port: u32) -> Result<SocketAddr> {
+    let config = AppConfig::load_for_test()?;
+    let addr = format!("[::1]:{}", port).parse()?;
+
+    let svc = NotificationService::new(config).await?.into_server();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(svc)
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+    sleep(Duration::from_micros(1)).await;
+
+    Ok(addr)
+}
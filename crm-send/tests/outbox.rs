@@ -0,0 +1,258 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use crm_send::{
+    pb::{
+        send_request::Msg, DeliveryStatus, GetDeadLetterRequest, GetStatusRequest, InAppMessage,
+        ListDeadLettersRequest, PurgeDeadLettersRequest, RequeueDeadLettersRequest, SendRequest,
+        SmsMessage, SubscribeRequest,
+    },
+    test_utils::get_test_pool,
+    AppConfig, NotificationService, Outbox, PgOutbox,
+};
+use futures::StreamExt;
+use tokio::time::timeout;
+use tonic::Code;
+
+#[tokio::test]
+async fn pending_messages_should_survive_restart() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let mut config = AppConfig::load()?;
+
+    // no workers: the service "crashes" before anything is delivered
+    config.outbox.workers = 0;
+    let outbox = Arc::new(PgOutbox::new(pool.clone(), config.outbox.lease()));
+    let service = NotificationService::with_outbox(config.clone(), outbox)?;
+    let msg = InAppMessage::fake();
+    let stream = tokio_stream::iter(vec![Ok(SendRequest::from(msg.clone()))]);
+    let ret: Vec<_> = service.send(stream).await?.into_inner().collect().await;
+    assert_eq!(ret.len(), 1);
+    assert!(ret[0].as_ref().unwrap().timestamp.is_some());
+    drop(service);
+
+    let (status,): (String,) =
+        sqlx::query_as("SELECT status::text FROM outbox WHERE message_id = $1")
+            .bind(&msg.message_id)
+            .fetch_one(&pool)
+            .await?;
+    assert_eq!(status, "queued");
+
+    // restarted service drains what the previous one accepted
+    config.outbox.workers = 1;
+    config.outbox.poll_interval_ms = 50;
+    let outbox = Arc::new(PgOutbox::new(pool.clone(), config.outbox.lease()));
+    let service = NotificationService::with_outbox(config, outbox)?;
+    let mut stream = service
+        .subscribe(SubscribeRequest {
+            device_id: msg.device_id.clone(),
+        })
+        .await?
+        .into_inner();
+    let received = timeout(Duration::from_secs(5), stream.next())
+        .await?
+        .expect("subscription closed")?;
+    assert_eq!(received, msg);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn in_app_message_should_be_delivered_once_received() -> Result<()> {
+    let mut config = AppConfig::load_for_test()?;
+    config.outbox.poll_interval_ms = 50;
+    let service = NotificationService::new(config).await?;
+    let msg = InAppMessage::fake();
+    let stream = tokio_stream::iter(vec![Ok(SendRequest::from(msg.clone()))]);
+    let ret: Vec<_> = service.send(stream).await?.into_inner().collect().await;
+    assert_eq!(ret.len(), 1);
+
+    // the device is offline, the message only waits in memory
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let status = get_status(&service, &msg.message_id).await?;
+    assert_eq!(status, DeliveryStatus::Queued);
+
+    let mut stream = service
+        .subscribe(SubscribeRequest {
+            device_id: msg.device_id.clone(),
+        })
+        .await?
+        .into_inner();
+    let received = timeout(Duration::from_secs(5), stream.next())
+        .await?
+        .expect("subscription closed")?;
+    assert_eq!(received, msg);
+
+    timeout(Duration::from_secs(5), async {
+        while get_status(&service, &msg.message_id).await? != DeliveryStatus::Delivered {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok::<_, anyhow::Error>(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn expired_lease_should_be_claimed_again() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let outbox = PgOutbox::new(pool, Duration::ZERO);
+
+    let msg: Msg = InAppMessage::fake().into();
+    outbox.push(&msg).await?;
+
+    let entries = outbox.claim(10).await?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].attempts, 1);
+
+    // the worker holding the message never completed it
+    let entries = outbox.claim(10).await?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].attempts, 2);
+
+    outbox
+        .complete(entries[0].id, DeliveryStatus::Delivered, &[])
+        .await?;
+    assert!(outbox.claim(10).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sms_should_be_stored_before_calling_the_provider() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let outbox = PgOutbox::new(pool, Duration::ZERO);
+
+    // the service crashed after storing the sms, before the provider answered
+    let msg: Msg = SmsMessage::fake().into();
+    outbox.push_claimed(&msg).await?;
+
+    let entries = outbox.claim(10).await?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].attempts, 2);
+    assert_eq!(entries[0].msg, msg);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn delivered_sms_should_be_completed_in_outbox() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let mut config = AppConfig::load()?;
+    config.outbox.workers = 0;
+    let outbox = Arc::new(PgOutbox::new(pool, config.outbox.lease()));
+    let service = NotificationService::with_outbox(config, outbox.clone())?;
+
+    let msg = SmsMessage::fake();
+    let stream = tokio_stream::iter(vec![Ok(SendRequest::from(msg.clone()))]);
+    let ret: Vec<_> = service.send(stream).await?.into_inner().collect().await;
+    assert_eq!(ret[0].as_ref().unwrap().status(), DeliveryStatus::Delivered);
+
+    let statuses = outbox
+        .get_status(std::slice::from_ref(&msg.message_id))
+        .await?;
+    assert_eq!(statuses[0].status(), DeliveryStatus::Delivered);
+    assert_eq!(statuses[0].attempts, 1);
+    assert_eq!(statuses[0].results.len(), msg.recipients.len());
+    assert!(outbox.claim(10).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn retried_message_should_wait_for_backoff() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let outbox = PgOutbox::new(pool, Duration::from_secs(60));
+
+    let msg: Msg = InAppMessage::fake().into();
+    outbox.push(&msg).await?;
+
+    let entries = outbox.claim(10).await?;
+    outbox
+        .retry(entries[0].id, "connection reset", Duration::from_secs(3600))
+        .await?;
+    assert!(outbox.claim(10).await?.is_empty());
+
+    let statuses = outbox.get_status(&[msg.message_id().to_string()]).await?;
+    assert_eq!(statuses[0].status(), DeliveryStatus::Queued);
+    assert_eq!(statuses[0].last_error, "connection reset");
+
+    outbox
+        .retry(entries[0].id, "connection reset", Duration::ZERO)
+        .await?;
+    let entries = outbox.claim(10).await?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].attempts, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn dead_letters_should_be_requeued_and_purged() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let mut config = AppConfig::load()?;
+    config.outbox.workers = 0;
+    let outbox = Arc::new(PgOutbox::new(pool, config.outbox.lease()));
+    let service = NotificationService::with_outbox(config, outbox.clone())?;
+
+    let msg: Msg = InAppMessage::fake().into();
+    let message_id = msg.message_id().to_string();
+    outbox.push(&msg).await?;
+    let entries = outbox.claim(10).await?;
+    outbox.fail(entries[0].id, "mailbox unavailable").await?;
+
+    let ret = service
+        .list_dead_letters(ListDeadLettersRequest::default())
+        .await?
+        .into_inner();
+    assert_eq!(ret.dead_letters.len(), 1);
+
+    let dead_letter = service
+        .get_dead_letter(GetDeadLetterRequest {
+            message_id: message_id.clone(),
+        })
+        .await?
+        .into_inner();
+    assert_eq!(dead_letter.attempts, 1);
+    assert_eq!(dead_letter.last_error, "mailbox unavailable");
+    assert_eq!(dead_letter.request.unwrap().msg, Some(msg));
+
+    let ret = service
+        .requeue_dead_letters(RequeueDeadLettersRequest {
+            message_ids: vec![message_id.clone()],
+        })
+        .await?
+        .into_inner();
+    assert_eq!(ret.count, 1);
+    let entries = outbox.claim(10).await?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].attempts, 1);
+
+    outbox.fail(entries[0].id, "mailbox unavailable").await?;
+    let ret = service
+        .purge_dead_letters(PurgeDeadLettersRequest {
+            message_ids: vec![],
+            all: true,
+        })
+        .await?
+        .into_inner();
+    assert_eq!(ret.count, 1);
+
+    let err = service
+        .get_dead_letter(GetDeadLetterRequest { message_id })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+
+    Ok(())
+}
+
+async fn get_status(service: &NotificationService, message_id: &str) -> Result<DeliveryStatus> {
+    let ret = service
+        .get_status(GetStatusRequest {
+            message_ids: vec![message_id.to_string()],
+        })
+        .await?
+        .into_inner();
+    Ok(ret.statuses[0].status())
+}
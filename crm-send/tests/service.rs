@@ -37,10 +37,10 @@ async fn test_send() -> Result<()> {
 }
 
 async fn start_server() -> Result<SocketAddr> {
-    let config = AppConfig::load()?;
+    let config = AppConfig::load_for_test()?;
     let addr = format!("[::1]:{}", config.server.port).parse()?;
 
-    let svc = NotificationService::new(config).into_server();
+    let svc = NotificationService::new(config).await?.into_server();
     tokio::spawn(async move {
         Server::builder()
             .add_service(svc)
@@ -0,0 +1,264 @@
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use crm_send::{
+    pb::{DeliveryStatus, RecipientStatus, SendRequest, SendResponse, SmsMessage},
+    AppConfig, NotificationService, SmsConfig, WebhookConfig, WebhookPayload, WebhookResponse,
+    WebhookTimeouts,
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+#[derive(Debug)]
+struct HttpRequest {
+    headers: Vec<(String, String)>,
+    body: Value,
+}
+
+#[tokio::test]
+async fn webhook_provider_should_map_recipient_results() -> Result<()> {
+    let response = json!({
+        "data": [
+            { "to": "+8613800000001", "state": "queued" },
+            { "to": "+8613800000002", "state": "bad_number" },
+            { "to": "+8613800000003", "state": "blocked" },
+        ]
+    });
+    let (addr, mut rx) = start_http_mock(response).await?;
+
+    let mut config = AppConfig::load_for_test()?;
+    config.sms = SmsConfig::Webhook(Box::new(WebhookConfig {
+        url: format!("http://{}/sms", addr),
+        auth_header: Some("X-Api-Key".to_string()),
+        auth_value: Some("secret".to_string()),
+        payload: WebhookPayload {
+            message_id: "id".to_string(),
+            sender: "from".to_string(),
+            recipients: "to".to_string(),
+            body: "text".to_string(),
+        },
+        response: WebhookResponse {
+            results: "data".to_string(),
+            recipient: "to".to_string(),
+            status: "state".to_string(),
+            accepted: vec!["queued".to_string()],
+            invalid_number: vec!["bad_number".to_string()],
+        },
+        timeouts: Default::default(),
+    }));
+
+    let service = NotificationService::new(config).await?;
+    let sms = SmsMessage {
+        message_id: "sms-1".to_string(),
+        sender: "10690000".to_string(),
+        recipients: vec![
+            "+8613800000001".to_string(),
+            "+8613800000002".to_string(),
+            "+8613800000003".to_string(),
+            "+8613800000004".to_string(),
+        ],
+        body: "Hello, world!".to_string(),
+    };
+    let stream = tokio_stream::iter(vec![Ok(SendRequest::from(sms))]);
+    let ret = service
+        .send(stream)
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(ret.len(), 1);
+
+    let res = ret.into_iter().next().unwrap()?;
+    assert_eq!(res.message_id, "sms-1");
+    let statuses: Vec<_> = res.results.iter().map(|r| r.status()).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            RecipientStatus::Accepted,
+            RecipientStatus::InvalidNumber,
+            RecipientStatus::Rejected,
+            RecipientStatus::Rejected,
+        ]
+    );
+
+    let req = rx.recv().await.expect("http mock closed");
+    assert!(req
+        .headers
+        .iter()
+        .any(|(k, v)| k.eq_ignore_ascii_case("x-api-key") && v == "secret"));
+    assert_eq!(req.body["id"], "sms-1");
+    assert_eq!(req.body["from"], "10690000");
+    assert_eq!(req.body["to"].as_array().unwrap().len(), 4);
+    assert_eq!(req.body["text"], "Hello, world!");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn webhook_provider_errors_should_fail_the_message() -> Result<()> {
+    let (addr, _rx) = start_http_mock(json!({ "unexpected": true })).await?;
+
+    let mut config = AppConfig::load_for_test()?;
+    config.sms = SmsConfig::Webhook(Box::new(WebhookConfig {
+        url: format!("http://{}/sms", addr),
+        auth_header: None,
+        auth_value: None,
+        payload: Default::default(),
+        response: Default::default(),
+        timeouts: Default::default(),
+    }));
+
+    let service = NotificationService::new(config).await?;
+    let stream = tokio_stream::iter(vec![Ok(SendRequest::from(SmsMessage::fake()))]);
+    let ret = service
+        .send(stream)
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(ret.len(), 1);
+    assert!(ret[0].is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn rate_limited_sms_should_be_queued_for_retry() -> Result<()> {
+    let (addr, _rx) = start_http_mock_with(
+        "429 Too Many Requests",
+        "Retry-After: 120\r\n",
+        json!({ "error": "slow down" }),
+    )
+    .await?;
+    let res = send_fake_sms(addr, Default::default()).await?;
+    assert_eq!(res.status(), DeliveryStatus::Queued);
+    Ok(())
+}
+
+#[tokio::test]
+async fn hung_provider_should_time_out_and_be_retried() -> Result<()> {
+    // accepts connections but never answers
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        let mut streams = vec![];
+        while let Ok((stream, _)) = listener.accept().await {
+            streams.push(stream);
+        }
+    });
+
+    let timeouts = WebhookTimeouts {
+        connect_ms: 100,
+        request_ms: 200,
+    };
+    let res = send_fake_sms(addr, timeouts).await?;
+    assert_eq!(res.status(), DeliveryStatus::Queued);
+    Ok(())
+}
+
+async fn send_fake_sms(addr: SocketAddr, timeouts: WebhookTimeouts) -> Result<SendResponse> {
+    let mut config = AppConfig::load_for_test()?;
+    config.sms = SmsConfig::Webhook(Box::new(WebhookConfig {
+        url: format!("http://{}/sms", addr),
+        auth_header: None,
+        auth_value: None,
+        payload: Default::default(),
+        response: Default::default(),
+        timeouts,
+    }));
+
+    let service = NotificationService::new(config).await?;
+    let stream = tokio_stream::iter(vec![Ok(SendRequest::from(SmsMessage::fake()))]);
+    let mut ret = service
+        .send(stream)
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(ret.len(), 1);
+    Ok(ret.remove(0)?)
+}
+
+/// a minimal http server answering every request with the given json
+async fn start_http_mock(response: Value) -> Result<(SocketAddr, mpsc::Receiver<HttpRequest>)> {
+    start_http_mock_with("200 OK", "", response).await
+}
+
+/// like `start_http_mock`, with the given status line and extra headers
+async fn start_http_mock_with(
+    status: &'static str,
+    headers: &'static str,
+    response: Value,
+) -> Result<(SocketAddr, mpsc::Receiver<HttpRequest>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (tx, rx) = mpsc::channel(16);
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let tx = tx.clone();
+            let response = response.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_http(stream, tx, status, headers, response).await {
+                    eprintln!("http mock error: {:?}", e);
+                }
+            });
+        }
+    });
+
+    Ok((addr, rx))
+}
+
+async fn handle_http(
+    stream: TcpStream,
+    tx: mpsc::Sender<HttpRequest>,
+    status: &str,
+    extra_headers: &str,
+    response: Value,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    let mut headers = vec![];
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    loop {
+        line.clear();
+        reader.read_line(&mut line).await?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((k, v)) = header.split_once(':') {
+            headers.push((k.trim().to_string(), v.trim().to_string()));
+        }
+    }
+
+    let len = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .map(|(_, v)| v.parse::<usize>())
+        .transpose()?
+        .unwrap_or_default();
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).await?;
+    let body = serde_json::from_slice(&body)?;
+    tx.send(HttpRequest { headers, body }).await?;
+
+    let response = response.to_string();
+    let reply = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        response.len(),
+        extra_headers,
+        response
+    );
+    writer.write_all(reply.as_bytes()).await?;
+    writer.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::Result;
+use crm_send::{
+    pb::{EmailMessage, SendRequest},
+    AppConfig, EmailConfig, NotificationService, SmtpConfig,
+};
+use futures::StreamExt;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    time::timeout,
+};
+
+#[derive(Debug, Default)]
+struct Mail {
+    from: String,
+    to: Vec<String>,
+    data: String,
+}
+
+#[tokio::test]
+async fn smtp_transport_should_deliver_mime() -> Result<()> {
+    let (addr, mut rx) = start_smtp_sink().await?;
+    let mut config = AppConfig::load_for_test()?;
+    config.email = EmailConfig::Smtp(SmtpConfig {
+        host: addr.ip().to_string(),
+        port: addr.port(),
+        starttls: false,
+        username: None,
+        password: None,
+    });
+
+    let service = NotificationService::new(config).await?;
+    let email = EmailMessage {
+        message_id: "d2b0a7e0-welcome".to_string(),
+        subject: "Welcome".to_string(),
+        sender: "crm@acme.org".to_string(),
+        recipients: vec!["alice@acme.org".to_string(), "bob@acme.org".to_string()],
+        body: "Hello, Alice!".to_string(),
+        html_body: String::new(),
+    };
+    let stream = tokio_stream::iter(vec![Ok(SendRequest::from(email))]);
+    let ret = service
+        .send(stream)
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(ret.len(), 1);
+
+    let mail = timeout(Duration::from_secs(5), rx.recv())
+        .await?
+        .expect("smtp sink closed");
+    assert_eq!(mail.from, "<crm@acme.org>");
+    assert_eq!(mail.to, vec!["<alice@acme.org>", "<bob@acme.org>"]);
+    assert!(mail.data.contains("Subject: Welcome"));
+    assert!(mail.data.contains("From: crm@acme.org"));
+    // recipients of a batched email are only in the envelope
+    assert!(!mail.data.contains("To: "));
+    assert!(mail
+        .data
+        .contains("Message-ID: <d2b0a7e0-welcome@crm-send>"));
+    assert!(mail.data.contains("Content-Type: text/plain"));
+    assert!(mail.data.contains("Hello, Alice!"));
+
+    Ok(())
+}
+
+#[test]
+fn html_email_should_be_multipart_alternative() -> Result<()> {
+    let email = EmailMessage {
+        message_id: "d2b0a7e0-recall".to_string(),
+        subject: "Recall".to_string(),
+        sender: "crm@acme.org".to_string(),
+        recipients: vec!["alice@acme.org".to_string()],
+        body: "Hello, Alice!".to_string(),
+        html_body: "<p>Hello, Alice!</p>".to_string(),
+    };
+    let mime = String::from_utf8(email.to_mime()?.formatted())?;
+    assert!(mime.contains("Content-Type: multipart/alternative"));
+    assert!(mime.contains("Content-Type: text/plain"));
+    assert!(mime.contains("Content-Type: text/html"));
+    assert!(mime.contains("<p>Hello, Alice!</p>"));
+
+    Ok(())
+}
+
+#[test]
+fn batched_email_should_hide_recipients() -> Result<()> {
+    let email = EmailMessage {
+        message_id: "d2b0a7e0-welcome".to_string(),
+        subject: "Welcome".to_string(),
+        sender: "crm@acme.org".to_string(),
+        recipients: vec!["alice@acme.org".to_string(), "bob@acme.org".to_string()],
+        body: "Hello!".to_string(),
+        html_body: String::new(),
+    };
+    let message = email.to_mime()?;
+    assert_eq!(message.envelope().to().len(), 2);
+    let mime = String::from_utf8(message.formatted())?;
+    assert!(!mime.contains("alice@acme.org"));
+    assert!(!mime.contains("bob@acme.org"));
+
+    Ok(())
+}
+
+/// a minimal in-process SMTP server which hands every received mail to the returned channel
+async fn start_smtp_sink() -> Result<(SocketAddr, mpsc::Receiver<Mail>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (tx, rx) = mpsc::channel(16);
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_smtp(stream, tx).await {
+                    eprintln!("smtp sink error: {:?}", e);
+                }
+            });
+        }
+    });
+
+    Ok((addr, rx))
+}
+
+async fn handle_smtp(stream: TcpStream, tx: mpsc::Sender<Mail>) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut mail = Mail::default();
+
+    writer.write_all(b"220 localhost ESMTP sink\r\n").await?;
+    while let Some(line) = lines.next_line().await? {
+        let cmd = line.to_ascii_uppercase();
+        let reply: &[u8] = if cmd.starts_with("EHLO") || cmd.starts_with("HELO") {
+            b"250 localhost\r\n"
+        } else if cmd.starts_with("MAIL FROM:") {
+            mail.from = line[10..].trim().to_string();
+            b"250 OK\r\n"
+        } else if cmd.starts_with("RCPT TO:") {
+            mail.to.push(line[8..].trim().to_string());
+            b"250 OK\r\n"
+        } else if cmd == "DATA" {
+            writer
+                .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                .await?;
+            while let Some(line) = lines.next_line().await? {
+                if line == "." {
+                    break;
+                }
+                mail.data.push_str(&line);
+                mail.data.push_str("\r\n");
+            }
+            tx.send(std::mem::take(&mut mail)).await?;
+            b"250 OK\r\n"
+        } else if cmd == "QUIT" {
+            writer.write_all(b"221 Bye\r\n").await?;
+            break;
+        } else if cmd == "RSET" || cmd == "NOOP" {
+            b"250 OK\r\n"
+        } else {
+            b"502 Command not implemented\r\n"
+        };
+        writer.write_all(reply).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::Result;
+use crm_send::{
+    pb::{
+        notification_client::NotificationClient, DeliveryStatus, EmailMessage, GetStatusRequest,
+        SendRequest, SmsMessage, WatchStatusRequest,
+    },
+    AppConfig, NotificationService,
+};
+use futures::StreamExt;
+use tokio::time::{sleep, timeout};
+use tonic::transport::Server;
+
+const PORT_BASE: u32 = 60200;
+
+#[tokio::test]
+async fn watch_status_should_follow_message_lifecycle() -> Result<()> {
+    let addr = start_server(PORT_BASE).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let mut watcher = client
+        .watch_status(WatchStatusRequest::default())
+        .await?
+        .into_inner();
+
+    let msg = EmailMessage::fake();
+    let reqs = tokio_stream::iter(vec![SendRequest::from(msg.clone())]);
+    let ret: Vec<_> = client.send(reqs).await?.into_inner().collect().await;
+    assert_eq!(ret[0].as_ref().unwrap().status(), DeliveryStatus::Queued);
+
+    let mut statuses = vec![];
+    while statuses.last() != Some(&DeliveryStatus::Delivered) {
+        let status = timeout(Duration::from_secs(5), watcher.next())
+            .await?
+            .expect("watcher closed")?;
+        assert_eq!(status.message_id, msg.message_id);
+        statuses.push(status.status());
+    }
+    assert_eq!(
+        statuses,
+        vec![
+            DeliveryStatus::Queued,
+            DeliveryStatus::Sending,
+            DeliveryStatus::Delivered
+        ]
+    );
+
+    let ret = client
+        .get_status(GetStatusRequest {
+            message_ids: vec![msg.message_id.clone(), "unknown".to_string()],
+        })
+        .await?
+        .into_inner();
+    assert_eq!(ret.statuses.len(), 1);
+    assert_eq!(ret.statuses[0].status(), DeliveryStatus::Delivered);
+    assert_eq!(ret.statuses[0].attempts, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_status_should_send_current_status_first() -> Result<()> {
+    let addr = start_server(PORT_BASE + 1).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let msg = SmsMessage::fake();
+    let reqs = tokio_stream::iter(vec![SendRequest::from(msg.clone())]);
+    let ret: Vec<_> = client.send(reqs).await?.into_inner().collect().await;
+    assert_eq!(ret[0].as_ref().unwrap().status(), DeliveryStatus::Delivered);
+
+    let mut watcher = client
+        .watch_status(WatchStatusRequest {
+            message_ids: vec![msg.message_id.clone()],
+        })
+        .await?
+        .into_inner();
+    let status = timeout(Duration::from_secs(5), watcher.next())
+        .await?
+        .expect("watcher closed")?;
+    assert_eq!(status.message_id, msg.message_id);
+    assert_eq!(status.status(), DeliveryStatus::Delivered);
+    assert_eq!(status.results.len(), msg.recipients.len());
+
+    Ok(())
+}
+
+async fn start_server(port: u32) -> Result<SocketAddr> {
+    let config = AppConfig::load_for_test()?;
+    let addr = format!("[::1]:{}", port).parse()?;
+
+    let svc = NotificationService::new(config).await?.into_server();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(svc)
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+    sleep(Duration::from_micros(1)).await;
+
+    Ok(addr)
+}
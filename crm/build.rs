@@ -9,9 +9,25 @@ fn main() -> Result<()> {
         .out_dir("src/pb")
         .with_derive_builder(&["WelcomeRequest", "RecallRequest", "RemindRequest"], None)
         .with_field_attributes(
-            &["WelcomeRequest.content_ids"],
+            &["WelcomeRequest.content_ids", "RecallRequest.content_ids"],
             &[r#"#[builder(setter(each(name="content_id", into)))]"#],
         )
+        .with_field_attributes(
+            &[
+                "WelcomeRequest.channels",
+                "RecallRequest.channels",
+                "RemindRequest.channels",
+            ],
+            &[r#"#[builder(setter(each(name="channel", into)), default)]"#],
+        )
+        .with_field_attributes(
+            &[
+                "WelcomeRequest.template",
+                "RecallRequest.template",
+                "RemindRequest.template",
+            ],
+            &[r#"#[builder(setter(into, strip_option), default)]"#],
+        )
         .compile(
             &["../protos/crm/messages.proto", "../protos/crm/rpc.proto"],
             &["../protos"],
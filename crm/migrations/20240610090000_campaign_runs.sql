@@ -0,0 +1,22 @@
+-- Add migration script here
+CREATE TYPE run_status AS ENUM(
+  'running',
+  'completed',
+  'failed'
+);
+
+-- campaign runs by the request id of the campaign call, a request sent again gets the
+-- original run back instead of running the campaign twice
+CREATE TABLE campaign_runs(
+  id text PRIMARY KEY,
+  campaign varchar(16) NOT NULL,
+  status run_status NOT NULL DEFAULT 'running',
+  sent int NOT NULL DEFAULT 0,
+  error text,
+  -- renewed while the run goes on, a running run past it belongs to a crashed service
+  locked_until timestamptz,
+  started_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  finished_at timestamptz
+);
+
+CREATE INDEX campaign_runs_finished_at_idx ON campaign_runs(finished_at);
@@ -0,0 +1,169 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use crm_send::pb::{send_request::Msg, SendRequest};
+
+/// a message sent to a batch of users, along with their emails
+#[derive(Debug)]
+pub struct Batch {
+    pub emails: Vec<String>,
+    pub req: SendRequest,
+}
+
+/// merges messages with the same content into one message to many recipients, so that bodies
+/// which turn out the same for many users don't flood the notification service with one-off
+/// messages
+#[derive(Debug)]
+pub struct Batcher {
+    max_recipients: usize,
+    pending: HashMap<Key, Batch>,
+}
+
+/// what a message looks like to its recipients, in-app messages are never batched as they are
+/// sent to a single device
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Email {
+        sender: String,
+        subject: String,
+        body: String,
+        html_body: String,
+    },
+    Sms {
+        sender: String,
+        body: String,
+    },
+}
+
+impl Batcher {
+    pub fn new(max_recipients: usize) -> Self {
+        Self {
+            max_recipients,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// add the message to the user, returns a batch once it is full or if the message can't be
+    /// batched
+    pub fn push(&mut self, email: String, req: SendRequest) -> Option<Batch> {
+        let batch = Batch {
+            emails: vec![email],
+            req,
+        };
+        let key = match batch.req.msg.as_ref().and_then(Key::new) {
+            Some(key) if self.max_recipients > 1 => key,
+            _ => return Some(batch),
+        };
+
+        match self.pending.entry(key) {
+            Entry::Vacant(entry) => {
+                entry.insert(batch);
+                None
+            }
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().merge(batch);
+                if entry.get().emails.len() >= self.max_recipients {
+                    Some(entry.remove())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// the batches which are not full yet
+    pub fn flush(self) -> impl Iterator<Item = Batch> {
+        self.pending.into_values()
+    }
+}
+
+impl Batch {
+    /// add the recipients of the other message, which must have the same content
+    fn merge(&mut self, other: Batch) {
+        self.emails.extend(other.emails);
+        match (&mut self.req.msg, other.req.msg) {
+            (Some(Msg::Email(email)), Some(Msg::Email(other))) => {
+                email.recipients.extend(other.recipients)
+            }
+            (Some(Msg::Sms(sms)), Some(Msg::Sms(other))) => sms.recipients.extend(other.recipients),
+            _ => unreachable!("only messages with the same key are merged"),
+        }
+    }
+}
+
+impl Key {
+    fn new(msg: &Msg) -> Option<Self> {
+        match msg {
+            Msg::Email(email) => Some(Key::Email {
+                sender: email.sender.clone(),
+                subject: email.subject.clone(),
+                body: email.body.clone(),
+                html_body: email.html_body.clone(),
+            }),
+            Msg::Sms(sms) => Some(Key::Sms {
+                sender: sms.sender.clone(),
+                body: sms.body.clone(),
+            }),
+            Msg::InApp(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crm_send::pb::{EmailMessage, InAppMessage};
+
+    fn email(to: &str, body: &str) -> (String, SendRequest) {
+        let msg = EmailMessage {
+            message_id: format!("{}-{}", to, body),
+            subject: "Welcome".to_string(),
+            sender: "crm@acme.org".to_string(),
+            recipients: vec![to.to_string()],
+            body: body.to_string(),
+            html_body: String::new(),
+        };
+        (to.to_string(), SendRequest::from(msg))
+    }
+
+    fn recipients(batch: &Batch) -> &[String] {
+        match &batch.req.msg {
+            Some(Msg::Email(email)) => &email.recipients,
+            _ => panic!("expected an email"),
+        }
+    }
+
+    #[test]
+    fn same_bodies_should_be_batched() {
+        let mut batcher = Batcher::new(2);
+        let (to, req) = email("alice@acme.org", "hi");
+        assert!(batcher.push(to, req).is_none());
+        let (to, req) = email("bob@acme.org", "hello bob");
+        assert!(batcher.push(to, req).is_none());
+        let (to, req) = email("carol@acme.org", "hi");
+        let batch = batcher.push(to, req).expect("batch should be full");
+        assert_eq!(batch.emails, ["alice@acme.org", "carol@acme.org"]);
+        assert_eq!(recipients(&batch), ["alice@acme.org", "carol@acme.org"]);
+
+        let rest: Vec<_> = batcher.flush().collect();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].emails, ["bob@acme.org"]);
+    }
+
+    #[test]
+    fn in_app_messages_should_not_be_batched() {
+        let mut batcher = Batcher::new(100);
+        let msg = InAppMessage {
+            message_id: "1".to_string(),
+            device_id: "device".to_string(),
+            title: "Welcome".to_string(),
+            body: "hi".to_string(),
+        };
+        let batch = batcher.push("alice@acme.org".to_string(), SendRequest::from(msg));
+        assert!(batch.is_some());
+        assert_eq!(batcher.flush().count(), 0);
+
+        let mut batcher = Batcher::new(1);
+        let (to, req) = email("alice@acme.org", "hi");
+        assert!(batcher.push(to, req).is_some());
+    }
+}
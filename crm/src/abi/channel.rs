@@ -0,0 +1,131 @@
+use std::slice;
+
+use crm_metadata::{
+    pb::{Content, Template},
+    Recipient, Tpl,
+};
+use crm_send::pb::SendRequest;
+use user_stat::pb::{Gender, NotificationChannel, User};
+
+use crate::pb::Channel;
+
+/// sender identities for each channel
+#[derive(Debug, Clone)]
+pub struct Senders {
+    pub email: String,
+    pub phone: String,
+}
+
+impl Channel {
+    /// valid channels requested by the client, fallback to email if none is given
+    pub fn resolve(channels: &[i32]) -> Vec<Channel> {
+        let mut ret: Vec<_> = channels
+            .iter()
+            .filter_map(|c| Channel::try_from(*c).ok())
+            .filter(|c| *c != Channel::Unspecified)
+            .collect();
+        ret.sort();
+        ret.dedup();
+
+        if ret.is_empty() {
+            ret.push(Channel::Email);
+        }
+        ret
+    }
+}
+
+impl From<Channel> for NotificationChannel {
+    fn from(channel: Channel) -> Self {
+        match channel {
+            Channel::Email => NotificationChannel::Email,
+            Channel::Sms => NotificationChannel::Sms,
+            Channel::InApp => NotificationChannel::InApp,
+            Channel::Unspecified => NotificationChannel::Unspecified,
+        }
+    }
+}
+
+impl Senders {
+    /// build one send request per channel the user could be reached on, rendered for the user
+    /// with the template variant of the user locale if given
+    pub fn send_requests(
+        &self,
+        subject: &str,
+        channels: &[Channel],
+        user: &User,
+        contents: &[Content],
+        unfinished: &[Content],
+        template: Option<&Template>,
+    ) -> Vec<SendRequest> {
+        let recipient = Recipient {
+            name: &user.name,
+            gender: gender(user.gender()),
+            unfinished,
+        };
+        let tpl = Tpl::new(contents)
+            .with_template(template)
+            .with_locale(&user.locale)
+            .with_recipient(recipient);
+        channels
+            .iter()
+            .filter_map(|channel| match channel {
+                Channel::Email => Some(SendRequest::new_email(
+                    subject,
+                    self.email.clone(),
+                    slice::from_ref(&user.email),
+                    &tpl,
+                )),
+                Channel::Sms => contact(&user.phone).map(|phone| {
+                    SendRequest::new_sms(self.phone.clone(), &[phone.to_string()], &tpl)
+                }),
+                Channel::InApp => contact(&user.device_id)
+                    .map(|device_id| SendRequest::new_in_app(subject, device_id.to_string(), &tpl)),
+                Channel::Unspecified => None,
+            })
+            .collect()
+    }
+}
+
+fn gender(gender: Gender) -> &'static str {
+    match gender {
+        Gender::Female => "female",
+        Gender::Male => "male",
+        Gender::Unknown => "unknown",
+    }
+}
+
+fn contact(v: &Option<String>) -> Option<&str> {
+    v.as_deref().filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_channels_should_work() {
+        assert_eq!(Channel::resolve(&[]), vec![Channel::Email]);
+        assert_eq!(Channel::resolve(&[0, 100]), vec![Channel::Email]);
+        assert_eq!(
+            Channel::resolve(&[3, 2, 3]),
+            vec![Channel::Sms, Channel::InApp]
+        );
+    }
+
+    #[test]
+    fn send_requests_should_skip_unreachable_channels() {
+        let senders = Senders {
+            email: "crm@acme.org".to_string(),
+            phone: "10690000".to_string(),
+        };
+        let user = User {
+            email: "alice@acme.org".to_string(),
+            name: "Alice".to_string(),
+            phone: Some("13800000000".to_string()),
+            ..Default::default()
+        };
+        let channels = [Channel::Email, Channel::Sms, Channel::InApp];
+        let reqs = senders.send_requests("Welcome", &channels, &user, &[], &[], None);
+        assert_eq!(reqs.len(), 2);
+    }
+}
@@ -0,0 +1,85 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crm_metadata::pb::{metadata_client::MetadataClient, Content, MaterializeRequest};
+use futures::StreamExt;
+use tonic::{transport::Channel, Status};
+use tracing::warn;
+use user_stat::pb::User;
+
+/// what a campaign sends to its users
+#[derive(Clone)]
+pub enum Contents {
+    /// the same contents for every user
+    Fixed(Arc<Vec<Content>>),
+    /// the contents each user started but hasn't finished, materialized per batch of users
+    Unfinished(MetadataClient<Channel>),
+}
+
+impl Contents {
+    /// materialize the contents once for the whole run
+    pub async fn fixed(metadata: &MetadataClient<Channel>, ids: &[u32]) -> Result<Self, Status> {
+        let contents = metadata
+            .clone()
+            .materialize(MaterializeRequest::new_with_ids(ids))
+            .await?
+            .into_inner();
+        let contents: Vec<Content> = contents
+            .filter_map(|v| async move { v.ok() })
+            .collect()
+            .await;
+        Ok(Self::Fixed(Arc::new(contents)))
+    }
+
+    /// call `f` with the contents and the unfinished contents of each user of the batch, users
+    /// with nothing to send are skipped
+    pub async fn each_user(
+        &self,
+        users: &[User],
+        mut f: impl FnMut(&User, &[Content], &[Content]),
+    ) {
+        let metadata = match self {
+            Self::Fixed(contents) => {
+                for user in users {
+                    f(user, contents, &[]);
+                }
+                return;
+            }
+            Self::Unfinished(metadata) => metadata,
+        };
+
+        let ids: Vec<u32> = users
+            .iter()
+            .flat_map(|user| user.started_but_not_finished.iter().map(|id| *id as u32))
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+        let contents = match metadata
+            .clone()
+            .materialize(MaterializeRequest::new_with_ids(&ids))
+            .await
+        {
+            Ok(contents) => contents.into_inner(),
+            Err(e) => {
+                warn!("Failed to materialize contents: {:?}", e);
+                return;
+            }
+        };
+        let contents: HashMap<u32, Content> = contents
+            .filter_map(|v| async move { v.ok() })
+            .map(|content| (content.id, content))
+            .collect()
+            .await;
+
+        for user in users {
+            let unfinished: Vec<Content> = user
+                .started_but_not_finished
+                .iter()
+                .filter_map(|id| contents.get(&(*id as u32)).cloned())
+                .collect();
+            if !unfinished.is_empty() {
+                f(user, &unfinished, &unfinished);
+            }
+        }
+    }
+}
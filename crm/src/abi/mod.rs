@@ -1,71 +1,282 @@
 pub mod auth;
+mod batch;
+mod channel;
+mod contents;
+mod run;
 
 use crate::{
-    pb::{WelcomeRequest, WelcomeResponse},
+    pb::{
+        Channel, RecallRequest, RecallResponse, RemindRequest, RemindResponse, TemplateRef,
+        WelcomeRequest, WelcomeResponse,
+    },
     CrmService,
 };
-use chrono::{Duration, Utc};
-use crm_metadata::pb::{Content, MaterializeRequest};
+use chrono::{DateTime, Duration, Utc};
+use crm_metadata::pb::{GetTemplateRequest, Template};
 use crm_send::pb::SendRequest;
-use futures::StreamExt;
-use std::sync::Arc;
+use futures::{future, StreamExt};
+use prost_types::Timestamp;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Response, Status};
 use tracing::warn;
-use user_stat::pb::QueryRequest;
+use user_stat::pb::{MarkNotifiedRequest, NotificationChannel, QueryRequest, TimeQuery};
+
+pub use batch::{Batch, Batcher};
+pub use channel::Senders;
+pub use contents::Contents;
+pub use run::CampaignRuns;
+
+// number of users whose messages are built at once, e.g. whose unfinished contents are
+// materialized in one metadata call
+const USER_BATCH_SIZE: usize = 100;
+// number of users marked notified in one user-stat call
+const MARK_NOTIFIED_BATCH_SIZE: usize = 1000;
 
 impl CrmService {
     pub async fn welcome(&self, req: WelcomeRequest) -> Result<Response<WelcomeResponse>, Status> {
-        let request_id = req.id;
+        let id = req.id.clone();
+        let svc = self.clone();
+        let (run, duplicate) = self
+            .runs
+            .run_once("welcome", &id, async move { svc.run_welcome(req).await })
+            .await?;
+        Ok(Response::new(WelcomeResponse {
+            id,
+            run: Some(run),
+            duplicate,
+        }))
+    }
+
+    pub async fn recall(&self, req: RecallRequest) -> Result<Response<RecallResponse>, Status> {
+        let id = req.id.clone();
+        let svc = self.clone();
+        let (run, duplicate) = self
+            .runs
+            .run_once("recall", &id, async move { svc.run_recall(req).await })
+            .await?;
+        Ok(Response::new(RecallResponse {
+            id,
+            run: Some(run),
+            duplicate,
+        }))
+    }
+
+    pub async fn remind(&self, req: RemindRequest) -> Result<Response<RemindResponse>, Status> {
+        let id = req.id.clone();
+        let svc = self.clone();
+        let (run, duplicate) = self
+            .runs
+            .run_once("remind", &id, async move { svc.run_remind(req).await })
+            .await?;
+        Ok(Response::new(RemindResponse {
+            id,
+            run: Some(run),
+            duplicate,
+        }))
+    }
+
+    async fn run_welcome(&self, req: WelcomeRequest) -> Result<u32, Status> {
         let d1 = Utc::now() - Duration::days(req.interval as _);
         let d2 = d1 + Duration::days(1);
+        let contents = Contents::fixed(&self.metadata, &req.content_ids).await?;
         let query = QueryRequest::new_with_dt("created_at", d1, d2);
-        let mut res_user_stats = self.user_stats.clone().query(query).await?.into_inner();
+        self.run_campaign("Welcome", &req.channels, req.template, query, contents)
+            .await
+    }
+
+    async fn run_recall(&self, req: RecallRequest) -> Result<u32, Status> {
+        let d1 = Utc::now() - Duration::days(req.last_visit_interval as _);
+        let d2 = d1 + Duration::days(1);
+        let contents = Contents::fixed(&self.metadata, &req.content_ids).await?;
+        let query = QueryRequest::new_with_dt("last_visited_at", d1, d2);
+        self.run_campaign("Recall", &req.channels, req.template, query, contents)
+            .await
+    }
+
+    async fn run_remind(&self, req: RemindRequest) -> Result<u32, Status> {
+        let d1 = Utc::now() - Duration::days(req.last_visit_interval as _);
+        let d2 = d1 + Duration::days(1);
+        let contents = Contents::Unfinished(self.metadata.clone());
+        let query = QueryRequest::new_with_dt("last_visited_at", d1, d2);
+        self.run_campaign("Remind", &req.channels, req.template, query, contents)
+            .await
+    }
+
+    /// send the contents to the users matching the query on each channel, skipping the users
+    /// notified on it within the cool-down window. Returns the number of users notified.
+    async fn run_campaign(
+        &self,
+        subject: &'static str,
+        channels: &[i32],
+        template: Option<TemplateRef>,
+        query: QueryRequest,
+        contents: Contents,
+    ) -> Result<u32, Status> {
+        let template = self.template(template).await?;
+
+        let mut sent = 0;
+        for channel in Channel::resolve(channels) {
+            let query = self.skip_notified(query.clone(), channel);
+            let res_user_stats = self.user_stats.clone().query(query).await?.into_inner();
+
+            let (tx, rx) = mpsc::channel(1024);
+
+            let senders = self.senders();
+            let contents = contents.clone();
+            let template = template.clone();
+            tokio::spawn(async move {
+                let mut batches = res_user_stats
+                    .filter_map(|v| future::ready(v.ok()))
+                    .chunks(USER_BATCH_SIZE);
+
+                while let Some(users) = batches.next().await {
+                    let mut reqs = Vec::new();
+                    contents
+                        .each_user(&users, |user, contents, unfinished| {
+                            let user_reqs = senders.send_requests(
+                                subject,
+                                &[channel],
+                                user,
+                                contents,
+                                unfinished,
+                                template.as_deref(),
+                            );
+                            reqs.extend(user_reqs.into_iter().map(|req| (user.email.clone(), req)));
+                        })
+                        .await;
+                    for req in reqs {
+                        if let Err(e) = tx.send(req).await {
+                            warn!("Failed to send message: {:?}", e);
+                        }
+                    }
+                }
+            });
+
+            sent += self.send_all(channel, rx).await?;
+        }
+        Ok(sent)
+    }
 
-        let contents = self
+    /// fetch the referenced template once for the whole run
+    async fn template(
+        &self,
+        template: Option<TemplateRef>,
+    ) -> Result<Option<Arc<Template>>, Status> {
+        let Some(TemplateRef { name, version }) = template else {
+            return Ok(None);
+        };
+        let template = self
             .metadata
             .clone()
-            .materialize(MaterializeRequest::new_with_ids(&req.content_ids))
+            .get_template(GetTemplateRequest { name, version })
             .await?
             .into_inner();
+        Ok(Some(Arc::new(template)))
+    }
 
-        let contents: Vec<Content> = contents
-            .filter_map(|v| async move { v.ok() })
-            .collect()
-            .await;
-        let contents = Arc::new(contents);
-
-        let (tx, rx) = mpsc::channel(1024);
-
-        let sender = self.config.server.sender_email.clone();
+    /// send the messages of the channel, each along with the email of its recipient. Messages with
+    /// the same content are batched into one message to many recipients. Returns the number of
+    /// users the messages accepted by the notification service were sent to, they are marked as
+    /// notified on the channel.
+    async fn send_all(
+        &self,
+        channel: Channel,
+        mut rx: mpsc::Receiver<(String, SendRequest)>,
+    ) -> Result<u32, Status> {
+        let (batch_tx, batch_rx) = mpsc::channel(1024);
+        let mut batcher = Batcher::new(self.config.campaign.max_recipients);
         tokio::spawn(async move {
-            while let Some(Ok(user)) = res_user_stats.next().await {
-                let contents = contents.clone();
-                let sender = sender.clone();
-                let tx = tx.clone();
-
-                let req = SendRequest::new("Welcome".to_string(), sender, &[user.email], &contents);
-                if let Err(e) = tx.send(req).await {
-                    warn!("Failed to send message: {:?}", e);
+            while let Some((email, req)) = rx.recv().await {
+                let Some(batch) = batcher.push(email, req) else {
+                    continue;
+                };
+                if batch_tx.send(batch).await.is_err() {
+                    return;
+                }
+            }
+            for batch in batcher.flush() {
+                if batch_tx.send(batch).await.is_err() {
+                    return;
                 }
             }
         });
-        let reqs = ReceiverStream::new(rx);
-
-        // NOTE: this is an alternative solution
-        // let sender = self.config.server.sender_email.clone();
-        // let reqs = res.filter_map(move |v| {
-        //     let sender: String = sender.clone();
-        //     let contents = contents.clone();
-        //     async move {
-        //         let v = v.ok()?;
-        //         Some(gen_send_req("Welcome".to_string(), sender, v, &contents))
-        //     }
-        // });
-
-        self.notification.clone().send(reqs).await?;
-
-        Ok(Response::new(WelcomeResponse { id: request_id }))
+
+        let recipients = Arc::new(Mutex::new(HashMap::new()));
+        let recipients_clone = recipients.clone();
+        let reqs = ReceiverStream::new(batch_rx).map(move |Batch { emails, req }| {
+            if let Some(msg) = &req.msg {
+                let message_id = msg.message_id().to_string();
+                recipients_clone.lock().unwrap().insert(message_id, emails);
+            }
+            req
+        });
+
+        let mut responses = self.notification.clone().send(reqs).await?.into_inner();
+        let mut notified = Vec::new();
+        while let Some(ret) = responses.next().await {
+            let Ok(res) = ret else { continue };
+            if let Some(emails) = recipients.lock().unwrap().remove(&res.message_id) {
+                notified.extend(emails);
+            }
+        }
+
+        let sent = notified.len() as u32;
+        self.mark_notified(channel, notified).await;
+        Ok(sent)
+    }
+
+    /// record the notification time so the users are skipped within the cool-down window,
+    /// messages are out already so failures are only logged
+    async fn mark_notified(&self, channel: Channel, emails: Vec<String>) {
+        let notified_at = to_ts(Utc::now());
+        for emails in emails.chunks(MARK_NOTIFIED_BATCH_SIZE) {
+            let req = MarkNotifiedRequest {
+                channel: NotificationChannel::from(channel) as i32,
+                emails: emails.to_vec(),
+                notified_at: Some(notified_at.clone()),
+            };
+            if let Err(e) = self.user_stats.clone().mark_notified(req).await {
+                warn!("Failed to mark users notified on {:?}: {:?}", channel, e);
+            }
+        }
+    }
+
+    /// skip the users notified on the channel within the cool-down window
+    fn skip_notified(&self, mut query: QueryRequest, channel: Channel) -> QueryRequest {
+        let cooldown = self.config.campaign.cooldown_secs;
+        let Some(column) = NotificationChannel::from(channel).column() else {
+            return query;
+        };
+        if cooldown == 0 {
+            return query;
+        }
+
+        let upper = Utc::now() - Duration::seconds(cooldown as _);
+        let tq = TimeQuery {
+            lower: None,
+            upper: Some(to_ts(upper)),
+            include_null: true,
+        };
+        query.timestamps.insert(column.to_string(), tq);
+        query
+    }
+
+    fn senders(&self) -> Senders {
+        Senders {
+            email: self.config.server.sender_email.clone(),
+            phone: self.config.server.sender_phone.clone(),
+        }
+    }
+}
+
+fn to_ts(dt: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: dt.timestamp(),
+        nanos: dt.timestamp_subsec_nanos() as i32,
     }
 }
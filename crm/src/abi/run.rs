@@ -0,0 +1,200 @@
+use super::to_ts;
+use crate::pb::{CampaignRun, RunStatus};
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool};
+use std::{future::Future, time::Duration};
+use tokio::time::interval;
+use tonic::Status;
+use tracing::{info, warn};
+
+/// campaign runs recorded by request id in the `campaign_runs` table, so a retried request
+/// doesn't run the campaign twice, even across restarts
+#[derive(Clone)]
+pub struct CampaignRuns {
+    pool: PgPool,
+    retention: Duration,
+    lease: Duration,
+}
+
+#[derive(Debug, FromRow)]
+struct RunRow {
+    id: String,
+    campaign: String,
+    status: String,
+    sent: i32,
+    error: Option<String>,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+const RUN_COLUMNS: &str = "id, campaign, status::text, sent, error, started_at, finished_at";
+
+impl CampaignRuns {
+    /// finished runs are kept for `retention`, running ones renew their `lease` as they go on
+    pub fn new(pool: PgPool, retention: Duration, lease: Duration) -> Self {
+        Self {
+            pool,
+            retention,
+            lease,
+        }
+    }
+
+    /// run the campaign unless the request id has been run already, returns the run and
+    /// whether it is a duplicate. The run is spawned, so it goes on and gets recorded even if
+    /// the caller gives up waiting; a duplicate request then gets it back as running
+    pub async fn run_once(
+        &self,
+        campaign: &str,
+        id: &str,
+        run: impl Future<Output = Result<u32, Status>> + Send + 'static,
+    ) -> Result<(CampaignRun, bool), Status> {
+        if let Some(run) = self.start(campaign, id).await? {
+            info!("Campaign run {} has been seen already: {:?}", id, run);
+            return Ok((run, true));
+        }
+
+        let runs = self.clone();
+        let id = id.to_string();
+        let handle = tokio::spawn(async move {
+            let ret = runs.keep_alive(&id, run).await;
+            let run = runs.finish(&id, &ret).await?;
+            ret.map(|_| run)
+        });
+        let run = handle
+            .await
+            .map_err(|e| Status::internal(format!("Campaign run failed: {}", e)))??;
+        Ok((run, false))
+    }
+
+    /// record a new run of the campaign, returns the existing run if the id has been seen.
+    /// A failed run, or a running one whose lease expired as its service crashed, could be
+    /// started again.
+    pub async fn start(&self, campaign: &str, id: &str) -> Result<Option<CampaignRun>, Status> {
+        if id.is_empty() {
+            return Err(Status::invalid_argument("id is required"));
+        }
+
+        sqlx::query("DELETE FROM campaign_runs WHERE finished_at < now() - $1")
+            .bind(self.retention)
+            .execute(&self.pool)
+            .await
+            .map_err(internal)?;
+
+        let started = sqlx::query(
+            r#"
+            INSERT INTO campaign_runs(id, campaign, locked_until)
+            VALUES ($1, $2, now() + $3)
+            ON CONFLICT (id) DO UPDATE
+            SET status = 'running', sent = 0, error = NULL, locked_until = now() + $3,
+              started_at = now(), finished_at = NULL
+            WHERE campaign_runs.campaign = EXCLUDED.campaign
+              AND (campaign_runs.status = 'failed'
+                OR (campaign_runs.status = 'running' AND campaign_runs.locked_until < now()))
+            "#,
+        )
+        .bind(id)
+        .bind(campaign)
+        .bind(self.lease)
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+        if started.rows_affected() > 0 {
+            return Ok(None);
+        }
+
+        let row: RunRow = sqlx::query_as(&format!(
+            "SELECT {} FROM campaign_runs WHERE id = $1",
+            RUN_COLUMNS
+        ))
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(internal)?;
+        if row.campaign != campaign {
+            return Err(Status::already_exists(format!(
+                "id {} is used by a {} run",
+                id, row.campaign
+            )));
+        }
+        Ok(Some(row.into()))
+    }
+
+    /// record the outcome of a run started before
+    pub async fn finish(&self, id: &str, ret: &Result<u32, Status>) -> Result<CampaignRun, Status> {
+        let (status, sent, error) = match ret {
+            Ok(sent) => ("completed", *sent as i32, None),
+            Err(e) => ("failed", 0, Some(e.message())),
+        };
+        let row: RunRow = sqlx::query_as(&format!(
+            r#"
+            UPDATE campaign_runs
+            SET status = $2::run_status, sent = $3, error = $4, locked_until = NULL,
+              finished_at = now()
+            WHERE id = $1
+            RETURNING {}
+            "#,
+            RUN_COLUMNS
+        ))
+        .bind(id)
+        .bind(status)
+        .bind(sent)
+        .bind(error)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(internal)?;
+        Ok(row.into())
+    }
+
+    /// drive the run while renewing its lease, so that it isn't taken for crashed
+    async fn keep_alive(
+        &self,
+        id: &str,
+        run: impl Future<Output = Result<u32, Status>>,
+    ) -> Result<u32, Status> {
+        tokio::pin!(run);
+        let mut renew = interval((self.lease / 3).max(Duration::from_millis(10)));
+        renew.tick().await;
+        loop {
+            tokio::select! {
+                ret = &mut run => return ret,
+                _ = renew.tick() => {
+                    let ret = sqlx::query(
+                        "UPDATE campaign_runs SET locked_until = now() + $2 WHERE id = $1",
+                    )
+                    .bind(id)
+                    .bind(self.lease)
+                    .execute(&self.pool)
+                    .await;
+                    if let Err(e) = ret {
+                        warn!("Failed to renew campaign run {}: {:?}", id, e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl From<RunRow> for CampaignRun {
+    fn from(row: RunRow) -> Self {
+        let status = match row.status.as_str() {
+            "running" => RunStatus::Running,
+            "completed" => RunStatus::Completed,
+            "failed" => RunStatus::Failed,
+            _ => RunStatus::Unspecified,
+        };
+        CampaignRun {
+            id: row.id,
+            campaign: row.campaign,
+            status: status as i32,
+            sent: row.sent.max(0) as u32,
+            error: row.error.unwrap_or_default(),
+            started_at: Some(to_ts(row.started_at)),
+            finished_at: row.finished_at.map(to_ts),
+        }
+    }
+}
+
+fn internal(e: sqlx::Error) -> Status {
+    warn!("Failed to record campaign run: {:?}", e);
+    Status::internal("Failed to record campaign run")
+}
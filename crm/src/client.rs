@@ -1,9 +1,10 @@
 use anyhow::Result;
 use crm::pb::{crm_client::CrmClient, WelcomeRequestBuilder};
 use tonic::{
-    metadata::MetadataValue,
+    metadata::{Ascii, MetadataValue},
+    service::Interceptor,
     transport::{Certificate, Channel, ClientTlsConfig},
-    Request,
+    Request, Status,
 };
 use uuid::Uuid;
 
@@ -21,10 +22,7 @@ async fn main() -> Result<()> {
     let token = include_str!("../../fixtures/token").trim();
     let token: MetadataValue<_> = format!("Bearer {}", token).parse()?;
 
-    let mut client = CrmClient::with_interceptor(channel, move |mut req: Request<()>| {
-        req.metadata_mut().insert("authorization", token.clone());
-        Ok(req)
-    });
+    let mut client = CrmClient::with_interceptor(channel, BearerToken(token));
 
     let req = WelcomeRequestBuilder::default()
         .id(Uuid::new_v4().to_string())
@@ -36,3 +34,13 @@ async fn main() -> Result<()> {
     println!("Response: {:?}", response);
     Ok(())
 }
+
+/// adds the token to every request
+struct BearerToken(MetadataValue<Ascii>);
+
+impl Interceptor for BearerToken {
+    fn call(&mut self, mut req: Request<()>) -> Result<Request<()>, Status> {
+        req.metadata_mut().insert("authorization", self.0.clone());
+        Ok(req)
+    }
+}
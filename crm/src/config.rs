@@ -6,6 +6,23 @@ use std::{env, fs::File};
 pub struct AppConfig {
     pub server: ServerConfig,
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub campaign: CampaignConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CampaignConfig {
+    /// how long a finished run is kept, a request id reused after that runs the campaign again
+    pub run_retention_secs: u64,
+    /// a running run is renewed within this long, or it is taken to be crashed and could be
+    /// started again
+    pub run_lease_secs: u64,
+    /// users notified on a channel within this window are skipped on that channel, 0 disables it
+    pub cooldown_secs: u64,
+    /// messages with the same content are sent as one message to up to this many users, 1 disables
+    /// batching
+    pub max_recipients: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,7 +33,10 @@ pub struct AuthConfig {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub port: u16,
+    /// where campaign runs are recorded
+    pub db_url: String,
     pub sender_email: String,
+    pub sender_phone: String,
     pub metadata: String,
     pub user_stats: String,
     pub notification: String,
@@ -29,6 +49,17 @@ pub struct TlsConfig {
     pub key: String,
 }
 
+impl Default for CampaignConfig {
+    fn default() -> Self {
+        Self {
+            run_retention_secs: 7 * 86400,
+            run_lease_secs: 60,
+            cooldown_secs: 86400,
+            max_recipients: 100,
+        }
+    }
+}
+
 impl AppConfig {
     pub fn load() -> Result<Self> {
         // read from  ./app.yml, or /etc/config/app.yml, or from env CHAT_CONFIG
@@ -1,4 +1,38 @@
 // This file is @generated by prost-build.
+/// a campaign run recorded by its request id
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CampaignRun {
+    /// request id of the run
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    /// campaign of the run: welcome, recall or remind
+    #[prost(string, tag = "2")]
+    pub campaign: ::prost::alloc::string::String,
+    #[prost(enumeration = "RunStatus", tag = "3")]
+    pub status: i32,
+    /// number of users the messages accepted by the notification service were
+    /// sent to
+    #[prost(uint32, tag = "4")]
+    pub sent: u32,
+    /// error of a failed run
+    #[prost(string, tag = "5")]
+    pub error: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "6")]
+    pub started_at: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "7")]
+    pub finished_at: ::core::option::Option<::prost_types::Timestamp>,
+}
+/// a template stored in the metadata service
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TemplateRef {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// version of the template, the published one if 0
+    #[prost(uint32, tag = "2")]
+    pub version: u32,
+}
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -12,12 +46,26 @@ pub struct WelcomeRequest {
     #[prost(uint32, repeated, tag = "3")]
     #[builder(setter(each(name = "content_id", into)))]
     pub content_ids: ::prost::alloc::vec::Vec<u32>,
+    /// channels to deliver the message through, email if empty
+    #[prost(enumeration = "Channel", repeated, tag = "4")]
+    #[builder(setter(each(name = "channel", into)), default)]
+    pub channels: ::prost::alloc::vec::Vec<i32>,
+    /// template to render the messages with, the builtin one if not set
+    #[prost(message, optional, tag = "5")]
+    #[builder(setter(into, strip_option), default)]
+    pub template: ::core::option::Option<TemplateRef>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct WelcomeResponse {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
+    /// the run of this id, the original one if the id has been seen already
+    #[prost(message, optional, tag = "2")]
+    pub run: ::core::option::Option<CampaignRun>,
+    /// true if the id has been seen already and nothing was sent this time
+    #[prost(bool, tag = "3")]
+    pub duplicate: bool,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -29,13 +77,28 @@ pub struct RecallRequest {
     #[prost(uint32, tag = "2")]
     pub last_visit_interval: u32,
     #[prost(uint32, repeated, tag = "3")]
+    #[builder(setter(each(name = "content_id", into)))]
     pub content_ids: ::prost::alloc::vec::Vec<u32>,
+    /// channels to deliver the message through, email if empty
+    #[prost(enumeration = "Channel", repeated, tag = "4")]
+    #[builder(setter(each(name = "channel", into)), default)]
+    pub channels: ::prost::alloc::vec::Vec<i32>,
+    /// template to render the messages with, the builtin one if not set
+    #[prost(message, optional, tag = "5")]
+    #[builder(setter(into, strip_option), default)]
+    pub template: ::core::option::Option<TemplateRef>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RecallResponse {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
+    /// the run of this id, the original one if the id has been seen already
+    #[prost(message, optional, tag = "2")]
+    pub run: ::core::option::Option<CampaignRun>,
+    /// true if the id has been seen already and nothing was sent this time
+    #[prost(bool, tag = "3")]
+    pub duplicate: bool,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -46,12 +109,92 @@ pub struct RemindRequest {
     pub id: ::prost::alloc::string::String,
     #[prost(uint32, tag = "2")]
     pub last_visit_interval: u32,
+    /// channels to deliver the message through, email if empty
+    #[prost(enumeration = "Channel", repeated, tag = "3")]
+    #[builder(setter(each(name = "channel", into)), default)]
+    pub channels: ::prost::alloc::vec::Vec<i32>,
+    /// template to render the messages with, the builtin one if not set
+    #[prost(message, optional, tag = "4")]
+    #[builder(setter(into, strip_option), default)]
+    pub template: ::core::option::Option<TemplateRef>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RemindResponse {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
+    /// the run of this id, the original one if the id has been seen already
+    #[prost(message, optional, tag = "2")]
+    pub run: ::core::option::Option<CampaignRun>,
+    /// true if the id has been seen already and nothing was sent this time
+    #[prost(bool, tag = "3")]
+    pub duplicate: bool,
+}
+/// channel a campaign message is delivered through
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Channel {
+    Unspecified = 0,
+    Email = 1,
+    Sms = 2,
+    InApp = 3,
+}
+impl Channel {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Channel::Unspecified => "CHANNEL_UNSPECIFIED",
+            Channel::Email => "CHANNEL_EMAIL",
+            Channel::Sms => "CHANNEL_SMS",
+            Channel::InApp => "CHANNEL_IN_APP",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "CHANNEL_UNSPECIFIED" => Some(Self::Unspecified),
+            "CHANNEL_EMAIL" => Some(Self::Email),
+            "CHANNEL_SMS" => Some(Self::Sms),
+            "CHANNEL_IN_APP" => Some(Self::InApp),
+            _ => None,
+        }
+    }
+}
+/// lifecycle of a campaign run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum RunStatus {
+    Unspecified = 0,
+    Running = 1,
+    Completed = 2,
+    Failed = 3,
+}
+impl RunStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            RunStatus::Unspecified => "RUN_STATUS_UNSPECIFIED",
+            RunStatus::Running => "RUN_STATUS_RUNNING",
+            RunStatus::Completed => "RUN_STATUS_COMPLETED",
+            RunStatus::Failed => "RUN_STATUS_FAILED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "RUN_STATUS_UNSPECIFIED" => Some(Self::Unspecified),
+            "RUN_STATUS_RUNNING" => Some(Self::Running),
+            "RUN_STATUS_COMPLETED" => Some(Self::Completed),
+            "RUN_STATUS_FAILED" => Some(Self::Failed),
+            _ => None,
+        }
+    }
 }
 /// Generated client implementations.
 pub mod crm_client {
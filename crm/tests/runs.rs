@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use crm::{pb::RunStatus, test_utils::get_test_pool, CampaignRuns};
+use tokio::{sync::oneshot, time::timeout};
+use tonic::{Code, Status};
+
+const RETENTION: Duration = Duration::from_secs(60);
+const LEASE: Duration = Duration::from_secs(60);
+
+#[tokio::test]
+async fn duplicate_run_should_return_original() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let runs = CampaignRuns::new(pool, RETENTION, LEASE);
+    assert!(runs.start("welcome", "r1").await?.is_none());
+
+    let run = runs.start("welcome", "r1").await?.unwrap();
+    assert_eq!(run.status(), RunStatus::Running);
+
+    runs.finish("r1", &Ok(3)).await?;
+    let run = runs.start("welcome", "r1").await?.unwrap();
+    assert_eq!(run.status(), RunStatus::Completed);
+    assert_eq!(run.sent, 3);
+
+    let err = runs.start("recall", "r1").await.unwrap_err();
+    assert_eq!(err.code(), Code::AlreadyExists);
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_run_could_be_started_again() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let runs = CampaignRuns::new(pool, RETENTION, LEASE);
+    assert!(runs.start("remind", "r1").await?.is_none());
+    let run = runs
+        .finish("r1", &Err(Status::unavailable("user-stat is down")))
+        .await?;
+    assert_eq!(run.status(), RunStatus::Failed);
+    assert_eq!(run.error, "user-stat is down");
+
+    assert!(runs.start("remind", "r1").await?.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn dropped_request_should_not_stop_the_run() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let runs = CampaignRuns::new(pool, RETENTION, LEASE);
+    let (tx, rx) = oneshot::channel::<u32>();
+    let run = runs.run_once(
+        "welcome",
+        "r1",
+        async move { Ok(rx.await.unwrap_or_default()) },
+    );
+    // the caller times out while the campaign is still sending
+    assert!(timeout(Duration::from_millis(100), run).await.is_err());
+
+    let (run, duplicate) = runs.run_once("welcome", "r1", async { Ok(0) }).await?;
+    assert!(duplicate);
+    assert_eq!(run.status(), RunStatus::Running);
+
+    tx.send(3).unwrap();
+    let run = timeout(Duration::from_secs(5), async {
+        loop {
+            let run = runs.start("welcome", "r1").await?.unwrap();
+            if run.status() != RunStatus::Running {
+                return Ok::<_, Status>(run);
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await??;
+    assert_eq!(run.status(), RunStatus::Completed);
+    assert_eq!(run.sent, 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn runs_should_survive_restart() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    let runs = CampaignRuns::new(pool.clone(), RETENTION, LEASE);
+    let (run, duplicate) = runs.run_once("recall", "r1", async { Ok(5) }).await?;
+    assert!(!duplicate);
+    assert_eq!(run.status(), RunStatus::Completed);
+    drop(runs);
+
+    let runs = CampaignRuns::new(pool, RETENTION, LEASE);
+    let (run, duplicate) = runs.run_once("recall", "r1", async { Ok(0) }).await?;
+    assert!(duplicate);
+    assert_eq!(run.sent, 5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn run_of_a_crashed_service_should_be_started_again() -> Result<()> {
+    let (_tdb, pool) = get_test_pool(None).await;
+    // the service crashed right after starting the run, so it was never renewed
+    let runs = CampaignRuns::new(pool.clone(), RETENTION, Duration::ZERO);
+    assert!(runs.start("remind", "r1").await?.is_none());
+
+    let runs = CampaignRuns::new(pool, RETENTION, LEASE);
+    let (run, duplicate) = runs.run_once("remind", "r1", async { Ok(2) }).await?;
+    assert!(!duplicate);
+    assert_eq!(run.status(), RunStatus::Completed);
+    Ok(())
+}
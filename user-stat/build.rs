@@ -13,7 +13,7 @@ fn main() -> Result<()> {
             true,
             Some(&[r#"#[serde(rename_all = "camelCase")]"#]),
         )
-        .with_sqlx_from_row(&["User"], None)
+        .with_sqlx_from_row(&["User", "UserProfile"], None)
         .with_derive_builder(
             &[
                 "User",
@@ -28,10 +28,71 @@ fn main() -> Result<()> {
             &["User.email", "User.name", "RawQueryRequest.query"],
             &[r#"#[builder(setter(into))]"#],
         )
+        .with_field_attributes(&["User.phone", "User.device_id"], &[r#"#[sqlx(default)]"#])
+        .with_field_attributes(
+            &["User.started_but_not_finished", "User.locale"],
+            &[
+                r#"#[builder(setter(into), default)]"#,
+                r#"#[sqlx(default)]"#,
+            ],
+        )
+        .with_field_attributes(
+            &["User.gender"],
+            &[
+                r#"#[builder(setter(into), default)]"#,
+                r#"#[sqlx(try_from = "crate::abi::DbGender", default)]"#,
+            ],
+        )
         .with_field_attributes(
             &["TimeQuery.before", "TimeQuery.after"],
             &[r#"#[builder(setter(into, strip_option))]"#],
         )
+        .with_field_attributes(&["TimeQuery.include_null"], &[r#"#[builder(default)]"#])
+        .with_field_attributes(
+            &["QueryRequest.filter"],
+            &[r#"#[builder(setter(into, strip_option), default)]"#],
+        )
+        .with_field_attributes(
+            &["QueryRequest.page_size", "QueryRequest.page_token"],
+            &[r#"#[builder(setter(into), default)]"#],
+        )
+        .with_field_attributes(
+            &["QueryRequest.fields"],
+            &[r#"#[builder(setter(each(name="field", into)), default)]"#],
+        )
+        .with_field_attributes(
+            &[
+                "UserProfile.name",
+                "UserProfile.locale",
+                "UserProfile.phone",
+                "UserProfile.device_id",
+            ],
+            &[r#"#[sqlx(default)]"#],
+        )
+        .with_field_attributes(
+            &["UserProfile.gender"],
+            &[r#"#[sqlx(try_from = "crate::abi::DbGender", default)]"#],
+        )
+        .with_field_attributes(
+            &[
+                "UserProfile.created_at",
+                "UserProfile.last_visited_at",
+                "UserProfile.last_watched_at",
+                "UserProfile.last_email_notification",
+                "UserProfile.last_in_app_notification",
+                "UserProfile.last_sms_notification",
+            ],
+            &[r#"#[sqlx(try_from = "crate::abi::DbTimestamp", default)]"#],
+        )
+        .with_field_attributes(
+            &[
+                "UserProfile.recent_watched",
+                "UserProfile.viewed_but_not_started",
+                "UserProfile.started_but_not_finished",
+                "UserProfile.finished",
+            ],
+            &[r#"#[sqlx(try_from = "crate::abi::DbIds", default)]"#],
+        )
         .with_field_attributes(
             &["QueryRequest.timestamps"],
             &[r#"#[builder(setter(each(name="timestamp", into)))]"#],
@@ -6,7 +6,10 @@ use std::{
 use anyhow::Result;
 use chrono::{DateTime, Days, Utc};
 use fake::{
-    faker::{chrono::en::DateTimeBetween, internet::en::SafeEmail, name::zh_cn::Name},
+    faker::{
+        chrono::en::DateTimeBetween, internet::en::SafeEmail, name::zh_cn::Name,
+        phone_number::zh_cn::CellNumber,
+    },
     Dummy, Fake, Faker,
 };
 use nanoid::nanoid;
@@ -50,6 +53,12 @@ struct UserStat {
     last_in_app_notification: DateTime<Utc>,
     #[dummy(faker = "DateTimeBetween(before(90), now())")]
     last_sms_notification: DateTime<Utc>,
+    #[dummy(faker = "CellNumber()")]
+    phone: String,
+    #[dummy(faker = "DeviceId")]
+    device_id: String,
+    #[dummy(faker = "Locale")]
+    locale: String,
 }
 
 #[tokio::main]
@@ -68,11 +77,11 @@ async fn main() -> Result<()> {
 async fn raw_insert(users: HashSet<UserStat>, pool: &PgPool) -> Result<()> {
     let mut sql = String::with_capacity(10 * 1000 * 1000);
     sql.push_str("
-    INSERT INTO user_stats(email, name, created_at, last_visited_at, last_watched_at, recent_watched, viewed_but_not_started, started_but_not_finished, finished, last_email_notification, last_in_app_notification, last_sms_notification)
+    INSERT INTO user_stats(email, name, created_at, last_visited_at, last_watched_at, recent_watched, viewed_but_not_started, started_but_not_finished, finished, last_email_notification, last_in_app_notification, last_sms_notification, phone, device_id, locale)
     VALUES");
     for user in users {
         sql.push_str(&format!(
-            "('{}', '{}', '{}', '{}', '{}', {}::int[], {}::int[], {}::int[], {}::int[], '{}', '{}', '{}'),",
+            "('{}', '{}', '{}', '{}', '{}', {}::int[], {}::int[], {}::int[], {}::int[], '{}', '{}', '{}', '{}', '{}', '{}'),",
             user.email,
             user.name,
             user.created_at,
@@ -85,6 +94,9 @@ async fn raw_insert(users: HashSet<UserStat>, pool: &PgPool) -> Result<()> {
             user.last_email_notification,
             user.last_in_app_notification,
             user.last_sms_notification,
+            user.phone,
+            user.device_id,
+            user.locale,
         ));
     }
 
@@ -104,8 +116,8 @@ async fn bulk_insert(users: HashSet<UserStat>, pool: &PgPool) -> Result<()> {
     for user in users {
         let query = sqlx::query(
            r#"
-            INSERT INTO user_stats(email, name, created_at, last_visited_at, last_watched_at, recent_watched, viewed_but_not_started, started_but_not_finished, finished, last_email_notification, last_in_app_notification, last_sms_notification)
-            VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            INSERT INTO user_stats(email, name, created_at, last_visited_at, last_watched_at, recent_watched, viewed_but_not_started, started_but_not_finished, finished, last_email_notification, last_in_app_notification, last_sms_notification, phone, device_id, locale)
+            VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
            "#
         )
         .bind(&user.email)
@@ -120,6 +132,9 @@ async fn bulk_insert(users: HashSet<UserStat>, pool: &PgPool) -> Result<()> {
         .bind(user.last_email_notification)
         .bind(user.last_in_app_notification)
         .bind(user.last_sms_notification)
+        .bind(&user.phone)
+        .bind(&user.device_id)
+        .bind(&user.locale)
         ;
         tx.execute(query).await?;
     }
@@ -167,3 +182,22 @@ impl Dummy<UniqueEmail> for String {
         format!("{}.{}{}", &email[..at], id, &email[at..])
     }
 }
+
+struct DeviceId;
+
+impl Dummy<DeviceId> for String {
+    fn dummy_with_rng<R: Rng + ?Sized>(_: &DeviceId, _rng: &mut R) -> String {
+        nanoid!(16, &ALPHABET)
+    }
+}
+
+struct Locale;
+
+// names are generated in chinese, so are most of the locales
+const LOCALES: [&str; 4] = ["zh-CN", "zh-CN", "zh-TW", "en-US"];
+
+impl Dummy<Locale> for String {
+    fn dummy_with_rng<R: Rng + ?Sized>(_: &Locale, rng: &mut R) -> String {
+        LOCALES[rng.gen_range(0..LOCALES.len())].to_string()
+    }
+}
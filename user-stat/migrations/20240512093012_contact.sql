@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE user_stats
+  ADD COLUMN phone varchar(32),
+  ADD COLUMN device_id varchar(64);
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE user_stats
+  ADD COLUMN locale varchar(16) NOT NULL DEFAULT 'en';
@@ -0,0 +1,5 @@
+-- Add migration script here
+UPDATE user_stats SET gender = 'unknown' WHERE gender IS NULL;
+
+ALTER TABLE user_stats
+  ALTER COLUMN gender SET NOT NULL;
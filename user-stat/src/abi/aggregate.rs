@@ -0,0 +1,213 @@
+use sqlx::{PgConnection, QueryBuilder};
+use tonic::{Response, Status};
+use tracing::info;
+
+use crate::{
+    pb::{
+        AggregateRequest, AggregateResponse, Bucket, CountResponse, Histogram, HistogramResult,
+        QueryRequest,
+    },
+    ServiceResult, UserStatsService,
+};
+
+impl UserStatsService {
+    pub async fn count(&self, query: QueryRequest) -> ServiceResult<CountResponse> {
+        let mut conn = self.inner.pool.acquire().await.map_err(aggregate_failed)?;
+        let count = count_users(&mut conn, &query).await?;
+        Ok(Response::new(CountResponse { count }))
+    }
+
+    pub async fn aggregate(&self, req: AggregateRequest) -> ServiceResult<AggregateResponse> {
+        let query = req.query.unwrap_or_default();
+        let mut histograms = Vec::with_capacity(req.histograms.len());
+        for h in req.histograms {
+            match Histogram::try_from(h) {
+                Ok(histogram) if histogram != Histogram::Unspecified => histograms.push(histogram),
+                _ => {
+                    return Err(Status::invalid_argument(format!(
+                        "Unknown histogram: {}",
+                        h
+                    )))
+                }
+            }
+        }
+        if histograms.is_empty() {
+            histograms = Histogram::ALL.to_vec();
+        }
+
+        // one snapshot for every query, so the buckets add up to the total under
+        // concurrent ingest
+        let mut tx = self.inner.pool.begin().await.map_err(aggregate_failed)?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .execute(&mut *tx)
+            .await
+            .map_err(aggregate_failed)?;
+        let total = count_users(&mut tx, &query).await?;
+        let mut ret = Vec::with_capacity(histograms.len());
+        for histogram in histograms {
+            let buckets = buckets(&mut tx, &query, histogram).await?;
+            ret.push(HistogramResult {
+                histogram: histogram as i32,
+                buckets,
+            });
+        }
+        tx.commit().await.map_err(aggregate_failed)?;
+
+        Ok(Response::new(AggregateResponse {
+            total,
+            histograms: ret,
+        }))
+    }
+}
+
+async fn count_users(conn: &mut PgConnection, query: &QueryRequest) -> Result<u64, Status> {
+    let mut builder = QueryBuilder::new("SELECT count(*) FROM user_stats WHERE TRUE");
+    query.push_conditions(&mut builder)?;
+    info!("Generated SQL: {}", builder.sql());
+
+    let (count,): (i64,) = builder
+        .build_query_as()
+        .fetch_one(conn)
+        .await
+        .map_err(|e| Status::internal(format!("Failed to count users: {}", e)))?;
+    Ok(count as u64)
+}
+
+async fn buckets(
+    conn: &mut PgConnection,
+    query: &QueryRequest,
+    histogram: Histogram,
+) -> Result<Vec<Bucket>, Status> {
+    let (group, key) = histogram.sql();
+    let mut builder = QueryBuilder::new(format!(
+        "SELECT {} AS key, count(*) FROM user_stats WHERE TRUE",
+        key
+    ));
+    query.push_conditions(&mut builder)?;
+    builder.push(format!(" GROUP BY {0} ORDER BY {0}", group));
+    info!("Generated SQL: {}", builder.sql());
+
+    let rows: Vec<(Option<String>, i64)> = builder
+        .build_query_as()
+        .fetch_all(conn)
+        .await
+        .map_err(aggregate_failed)?;
+    Ok(rows
+        .into_iter()
+        .map(|(key, count)| Bucket {
+            key: key.unwrap_or_default(),
+            count: count as u64,
+        })
+        .collect())
+}
+
+fn aggregate_failed(e: sqlx::Error) -> Status {
+    Status::internal(format!("Failed to aggregate users: {}", e))
+}
+
+impl Histogram {
+    const ALL: [Histogram; 4] = [
+        Histogram::Gender,
+        Histogram::CreatedDay,
+        Histogram::CreatedWeek,
+        Histogram::FinishedLength,
+    ];
+
+    /// the expression users are grouped and ordered by, and the text key of the bucket
+    fn sql(&self) -> (&'static str, &'static str) {
+        match self {
+            Histogram::Gender => ("gender", "gender::text"),
+            // days don't depend on the TimeZone of the session
+            Histogram::CreatedDay => (
+                "date_trunc('day', created_at AT TIME ZONE 'UTC')",
+                "to_char(date_trunc('day', created_at AT TIME ZONE 'UTC'), 'YYYY-MM-DD')",
+            ),
+            Histogram::CreatedWeek => (
+                "date_trunc('week', created_at AT TIME ZONE 'UTC')",
+                "to_char(date_trunc('week', created_at AT TIME ZONE 'UTC'), 'YYYY-MM-DD')",
+            ),
+            Histogram::FinishedLength => (
+                "coalesce(cardinality(finished), 0)",
+                "coalesce(cardinality(finished), 0)::text",
+            ),
+            Histogram::Unspecified => unreachable!("unspecified histograms are rejected"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::pb::{ArrayOp, Filter, QueryRequestBuilder};
+
+    #[tokio::test]
+    async fn count_should_match_query() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let query = QueryRequestBuilder::default()
+            .filter(Filter::length("finished", ArrayOp::LengthGt, 5))
+            .build()?;
+        let count = svc.count(query.clone()).await?.into_inner().count;
+        let users = svc.query(query).await?.into_inner().count().await;
+        assert!(count > 0);
+        assert_eq!(count, users as u64);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn aggregate_should_cover_every_user() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let ret = svc
+            .aggregate(AggregateRequest::default())
+            .await?
+            .into_inner();
+        assert_eq!(ret.total, 116);
+        assert_eq!(ret.histograms.len(), 4);
+        for histogram in &ret.histograms {
+            let sum: u64 = histogram.buckets.iter().map(|b| b.count).sum();
+            assert_eq!(sum, ret.total, "{:?}", histogram.histogram());
+        }
+
+        // fixture users have no gender
+        let gender = &ret.histograms[0];
+        assert_eq!(gender.histogram(), Histogram::Gender);
+        assert_eq!(gender.buckets.len(), 1);
+        assert_eq!(gender.buckets[0].key, "unknown");
+
+        let days = &ret.histograms[1].buckets;
+        assert!(days.windows(2).all(|w| w[0].key < w[1].key));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unspecified_histogram_should_be_rejected() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let req = AggregateRequest {
+            histograms: vec![Histogram::Unspecified as i32],
+            ..Default::default()
+        };
+        let err = svc.aggregate(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn created_day_should_not_depend_on_session_time_zone() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let query = QueryRequest::default();
+        let mut conn = svc.pool.acquire().await?;
+        sqlx::query("SET TimeZone = 'UTC'")
+            .execute(&mut *conn)
+            .await?;
+        let utc = buckets(&mut conn, &query, Histogram::CreatedDay).await?;
+
+        sqlx::query("SET TimeZone = 'Pacific/Kiritimati'")
+            .execute(&mut *conn)
+            .await?;
+        let ret = buckets(&mut conn, &query, Histogram::CreatedDay).await?;
+        assert_eq!(ret, utc);
+        Ok(())
+    }
+}
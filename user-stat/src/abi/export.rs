@@ -0,0 +1,473 @@
+use std::{mem, sync::Arc};
+
+use anyhow::Result;
+use arrow_array::{
+    builder::{Int32Builder, ListBuilder, StringBuilder, TimestampMicrosecondBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use parquet::arrow::ArrowWriter;
+use prost_types::Timestamp;
+use sqlx::{postgres::PgRow, FromRow, Row};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Response, Status};
+use tracing::info;
+
+use super::{import::format_ids, ts_to_utc, CHANNEL_SIZE};
+use crate::{
+    pb::{DataFormat, ExportChunk, ExportRequest, Gender, UserProfile},
+    ExportStream, ServiceResult, UserStatsService,
+};
+
+/// rows encoded before the bytes ready so far are sent as a chunk
+const CHUNK_ROWS: usize = 1000;
+
+/// rows of a parquet row group
+const ROW_GROUP_ROWS: usize = 8192;
+
+/// a column value of a profile
+enum Value<'a> {
+    Str(Option<&'a str>),
+    Time(Option<DateTime<Utc>>),
+    Ids(Option<&'a [i32]>),
+}
+
+/// a profile with its content id arrays as stored, so that a NULL array is exported as NULL
+/// rather than as an empty one, which a re-import would store
+#[derive(Clone, Default)]
+struct ExportRow {
+    profile: UserProfile,
+    recent_watched: Option<Vec<i32>>,
+    viewed_but_not_started: Option<Vec<i32>>,
+    started_but_not_finished: Option<Vec<i32>>,
+    finished: Option<Vec<i32>>,
+}
+
+/// writes profiles into a file of some format, bytes are handed out as they are ready
+trait Encoder: Send {
+    fn write(&mut self, row: &ExportRow) -> Result<()>;
+
+    /// the bytes encoded since the last call, some rows may still be buffered
+    fn take(&mut self) -> Result<Vec<u8>>;
+
+    /// the rest of the file
+    fn finish(self: Box<Self>) -> Result<Vec<u8>>;
+}
+
+impl UserStatsService {
+    pub async fn export(&self, req: ExportRequest) -> ServiceResult<ExportStream> {
+        let format = req.format();
+        let query = req.query.unwrap_or_default();
+        let columns = query.projection()?;
+        let builder = query.to_profile_query()?;
+        info!("Generated SQL: {}", builder.sql());
+
+        let mut encoder = new_encoder(format, columns).map_err(internal)?;
+        let mut rows = self.fetch_rows::<ExportRow>(builder);
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        tokio::spawn(async move {
+            let mut count = 0;
+            while let Some(row) = rows.next().await {
+                let ret = match row {
+                    Ok(row) => encoder.write(&row).map_err(internal),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = ret {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+
+                count += 1;
+                if count % CHUNK_ROWS == 0 {
+                    match encoder.take() {
+                        Ok(data) if data.is_empty() => {}
+                        Ok(data) => {
+                            if tx.send(Ok(ExportChunk { data })).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => {
+                            let _ = tx.send(Err(internal(e))).await;
+                            return;
+                        }
+                    }
+                }
+            }
+
+            let ret = encoder
+                .finish()
+                .map(|data| ExportChunk { data })
+                .map_err(internal);
+            let _ = tx.send(ret).await;
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+fn new_encoder(format: DataFormat, columns: Vec<&'static str>) -> Result<Box<dyn Encoder>> {
+    Ok(match format {
+        DataFormat::Csv => Box::new(CsvEncoder::new(columns)?),
+        DataFormat::Ndjson => Box::new(NdjsonEncoder {
+            columns,
+            buf: Vec::new(),
+        }),
+        DataFormat::Parquet => Box::new(ParquetEncoder::new(columns)?),
+    })
+}
+
+struct CsvEncoder {
+    columns: Vec<&'static str>,
+    writer: csv::Writer<Vec<u8>>,
+}
+
+impl CsvEncoder {
+    fn new(columns: Vec<&'static str>) -> Result<Self> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(&columns)?;
+        Ok(Self { columns, writer })
+    }
+}
+
+impl Encoder for CsvEncoder {
+    fn write(&mut self, row: &ExportRow) -> Result<()> {
+        let record = self.columns.iter().map(|c| match value(row, c) {
+            Value::Str(s) => s.unwrap_or_default().to_string(),
+            Value::Time(t) => t.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            Value::Ids(ids) => ids.map(format_ids).unwrap_or_default(),
+        });
+        self.writer.write_record(record)?;
+        Ok(())
+    }
+
+    fn take(&mut self) -> Result<Vec<u8>> {
+        // the writer buffers internally, so swap it for a new one to get the bytes out
+        let writer = mem::replace(&mut self.writer, csv::Writer::from_writer(Vec::new()));
+        Ok(writer.into_inner()?)
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Vec<u8>> {
+        self.take()
+    }
+}
+
+struct NdjsonEncoder {
+    columns: Vec<&'static str>,
+    buf: Vec<u8>,
+}
+
+impl Encoder for NdjsonEncoder {
+    fn write(&mut self, row: &ExportRow) -> Result<()> {
+        let object: serde_json::Map<_, _> = self
+            .columns
+            .iter()
+            .map(|c| {
+                let v = match value(row, c) {
+                    Value::Str(s) => s.into(),
+                    Value::Time(t) => t.map(|t| t.to_rfc3339()).into(),
+                    Value::Ids(ids) => ids.into(),
+                };
+                (c.to_string(), v)
+            })
+            .collect();
+        serde_json::to_writer(&mut self.buf, &object)?;
+        self.buf.push(b'\n');
+        Ok(())
+    }
+
+    fn take(&mut self) -> Result<Vec<u8>> {
+        Ok(mem::take(&mut self.buf))
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Vec<u8>> {
+        self.take()
+    }
+}
+
+struct ParquetEncoder {
+    columns: Vec<&'static str>,
+    schema: SchemaRef,
+    writer: ArrowWriter<Vec<u8>>,
+    rows: Vec<ExportRow>,
+}
+
+impl ParquetEncoder {
+    fn new(columns: Vec<&'static str>) -> Result<Self> {
+        let fields: Vec<_> = columns
+            .iter()
+            .map(|c| {
+                let data_type = match value(&ExportRow::default(), c) {
+                    Value::Str(_) => DataType::Utf8,
+                    Value::Time(_) => {
+                        DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+                    }
+                    Value::Ids(_) => DataType::new_list(DataType::Int32, true),
+                };
+                Field::new(*c, data_type, *c != "email")
+            })
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+        let writer = ArrowWriter::try_new(Vec::new(), schema.clone(), None)?;
+        Ok(Self {
+            columns,
+            schema,
+            writer,
+            rows: Vec::new(),
+        })
+    }
+
+    /// write the buffered rows as a row group
+    fn flush(&mut self) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+
+        let arrays = self
+            .columns
+            .iter()
+            .map(|c| self.array(c))
+            .collect::<Vec<_>>();
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+        self.writer.write(&batch)?;
+        self.writer.flush()?;
+        self.rows.clear();
+        Ok(())
+    }
+
+    fn array(&self, column: &str) -> ArrayRef {
+        let values = self.rows.iter().map(|p| value(p, column));
+        match value(&ExportRow::default(), column) {
+            Value::Str(_) => {
+                let mut builder = StringBuilder::new();
+                for v in values {
+                    if let Value::Str(s) = v {
+                        builder.append_option(s);
+                    }
+                }
+                Arc::new(builder.finish())
+            }
+            Value::Time(_) => {
+                let mut builder = TimestampMicrosecondBuilder::new().with_timezone("UTC");
+                for v in values {
+                    if let Value::Time(t) = v {
+                        builder.append_option(t.map(|t| t.timestamp_micros()));
+                    }
+                }
+                Arc::new(builder.finish())
+            }
+            Value::Ids(_) => {
+                let mut builder = ListBuilder::new(Int32Builder::new());
+                for v in values {
+                    if let Value::Ids(ids) = v {
+                        builder.append_option(ids.map(|ids| ids.iter().map(|id| Some(*id))));
+                    }
+                }
+                Arc::new(builder.finish())
+            }
+        }
+    }
+}
+
+impl Encoder for ParquetEncoder {
+    fn write(&mut self, row: &ExportRow) -> Result<()> {
+        self.rows.push(row.clone());
+        if self.rows.len() >= ROW_GROUP_ROWS {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn take(&mut self) -> Result<Vec<u8>> {
+        // the writer only appends, taking what has been written so far keeps the file intact
+        Ok(mem::take(self.writer.inner_mut()))
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Vec<u8>> {
+        self.flush()?;
+        let mut data = self.take()?;
+        data.extend(self.writer.into_inner()?);
+        Ok(data)
+    }
+}
+
+/// the value of the column, the column must be one of the profile columns
+fn value<'a>(row: &'a ExportRow, column: &str) -> Value<'a> {
+    let profile = &row.profile;
+    // timestamps read from the database are always valid
+    let time = |ts: &Option<Timestamp>| Value::Time(ts.as_ref().and_then(|ts| ts_to_utc(ts).ok()));
+    match column {
+        "email" => Value::Str(Some(&profile.email)),
+        "name" => Value::Str(Some(&profile.name)),
+        "gender" => Value::Str(Some(match profile.gender() {
+            Gender::Female => "female",
+            Gender::Male => "male",
+            Gender::Unknown => "unknown",
+        })),
+        "locale" => Value::Str(Some(&profile.locale)),
+        "phone" => Value::Str(profile.phone.as_deref()),
+        "device_id" => Value::Str(profile.device_id.as_deref()),
+        "created_at" => time(&profile.created_at),
+        "last_visited_at" => time(&profile.last_visited_at),
+        "last_watched_at" => time(&profile.last_watched_at),
+        "last_email_notification" => time(&profile.last_email_notification),
+        "last_in_app_notification" => time(&profile.last_in_app_notification),
+        "last_sms_notification" => time(&profile.last_sms_notification),
+        "recent_watched" => Value::Ids(row.recent_watched.as_deref()),
+        "viewed_but_not_started" => Value::Ids(row.viewed_but_not_started.as_deref()),
+        "started_but_not_finished" => Value::Ids(row.started_but_not_finished.as_deref()),
+        "finished" => Value::Ids(row.finished.as_deref()),
+        _ => unreachable!("unknown profile column {}", column),
+    }
+}
+
+impl FromRow<'_, PgRow> for ExportRow {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        // arrays which aren't projected are left out like the other profile fields
+        let ids = |column| match row.try_get(column) {
+            Err(sqlx::Error::ColumnNotFound(_)) => Ok(None),
+            ret => ret,
+        };
+        Ok(Self {
+            profile: UserProfile::from_row(row)?,
+            recent_watched: ids("recent_watched")?,
+            viewed_but_not_started: ids("viewed_but_not_started")?,
+            started_but_not_finished: ids("started_but_not_finished")?,
+            finished: ids("finished")?,
+        })
+    }
+}
+
+fn internal(e: anyhow::Error) -> Status {
+    Status::internal(format!("Failed to export users: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::*;
+    use crate::pb::{QueryRequest, QueryRequestBuilder};
+
+    async fn export(
+        svc: &UserStatsService,
+        query: QueryRequest,
+        format: DataFormat,
+    ) -> Result<Vec<u8>> {
+        let req = ExportRequest {
+            query: Some(query),
+            format: format as i32,
+        };
+        let mut stream = svc.export(req).await?.into_inner();
+        let mut data = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            data.extend(chunk?.data);
+        }
+        Ok(data)
+    }
+
+    #[tokio::test]
+    async fn export_should_write_every_format() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let query = QueryRequestBuilder::default()
+            .field("created_at")
+            .field("finished")
+            .build()?;
+
+        let csv = export(&svc, query.clone(), DataFormat::Csv).await?;
+        let mut reader = csv::Reader::from_reader(csv.as_slice());
+        assert_eq!(reader.headers()?, vec!["email", "created_at", "finished"]);
+        assert_eq!(reader.records().count(), 116);
+
+        let ndjson = export(&svc, query.clone(), DataFormat::Ndjson).await?;
+        let lines: Vec<serde_json::Value> = ndjson
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(lines.len(), 116);
+        assert!(lines[0]["finished"].is_array());
+
+        let parquet = export(&svc, query, DataFormat::Parquet).await?;
+        let path = std::env::temp_dir().join(format!("user-stat-{}.parquet", std::process::id()));
+        fs::write(&path, parquet)?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(&path)?)?.build()?;
+        let mut rows = 0;
+        for batch in reader {
+            let batch = batch?;
+            assert_eq!(batch.num_columns(), 3);
+            rows += batch.num_rows();
+        }
+        fs::remove_file(path)?;
+        assert_eq!(rows, 116);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn exported_csv_should_import_back() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let csv = export(&svc, QueryRequest::default(), DataFormat::Csv).await?;
+
+        let req = crate::pb::ImportRequest {
+            format: DataFormat::Csv as i32,
+            data: csv,
+        };
+        let ret = svc
+            .import(futures::stream::iter([Ok(req)]))
+            .await?
+            .into_inner();
+        assert_eq!((ret.inserted, ret.updated), (0, 116));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn null_arrays_should_be_exported_and_imported_as_null() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let (email,): (String,) = sqlx::query_as(
+            "UPDATE user_stats SET finished = NULL \
+             WHERE email = (SELECT email FROM user_stats LIMIT 1) RETURNING email",
+        )
+        .fetch_one(&svc.pool)
+        .await?;
+        let query = QueryRequestBuilder::default()
+            .field("name")
+            .field("finished")
+            .build()?;
+
+        let ndjson = export(&svc, query.clone(), DataFormat::Ndjson).await?;
+        let line: serde_json::Value = ndjson
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_slice)
+            .find(|v: &Result<serde_json::Value, _>| {
+                v.as_ref().is_ok_and(|v| v["email"] == email.as_str())
+            })
+            .expect("the user is exported")?;
+        assert!(line["finished"].is_null());
+
+        let csv = export(&svc, query, DataFormat::Csv).await?;
+        let mut reader = csv::Reader::from_reader(csv.as_slice());
+        let record = reader
+            .records()
+            .find(|r| r.as_ref().is_ok_and(|r| &r[0] == email.as_str()))
+            .expect("the user is exported")?;
+        assert_eq!(&record[2], "");
+
+        let req = crate::pb::ImportRequest {
+            format: DataFormat::Csv as i32,
+            data: csv,
+        };
+        svc.import(futures::stream::iter([Ok(req)])).await?;
+        let (finished,): (Option<Vec<i32>>,) =
+            sqlx::query_as("SELECT finished FROM user_stats WHERE email = $1")
+                .bind(&email)
+                .fetch_one(&svc.pool)
+                .await?;
+        assert!(finished.is_none());
+        Ok(())
+    }
+}
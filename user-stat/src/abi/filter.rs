@@ -0,0 +1,246 @@
+use std::ops;
+
+use chrono::{DateTime, Utc};
+use prost_types::Timestamp;
+use sqlx::{Postgres, QueryBuilder};
+use tonic::Status;
+
+use super::{column, ts_to_utc, DbGender, ID_COLUMNS, TIMESTAMP_COLUMNS};
+use crate::pb::{
+    filter::Filter as F, ArrayFilter, ArrayOp, Filter, FilterList, Gender, NullCheck, TimeRange,
+};
+
+/// columns a null check may be applied to
+const NULLABLE_COLUMNS: &[&str] = &[
+    "created_at",
+    "last_visited_at",
+    "last_watched_at",
+    "last_email_notification",
+    "last_in_app_notification",
+    "last_sms_notification",
+    "recent_watched",
+    "viewed_but_not_started",
+    "started_but_not_finished",
+    "finished",
+    "phone",
+    "device_id",
+];
+
+type Builder = QueryBuilder<'static, Postgres>;
+
+impl Filter {
+    pub fn and(filters: impl IntoIterator<Item = Filter>) -> Self {
+        let filters = filters.into_iter().collect();
+        F::And(FilterList { filters }).into()
+    }
+
+    pub fn or(filters: impl IntoIterator<Item = Filter>) -> Self {
+        let filters = filters.into_iter().collect();
+        F::Or(FilterList { filters }).into()
+    }
+
+    pub fn time(field: &str, lower: Option<DateTime<Utc>>, upper: Option<DateTime<Utc>>) -> Self {
+        F::Time(TimeRange {
+            field: field.to_string(),
+            lower: lower.map(utc_to_ts),
+            upper: upper.map(utc_to_ts),
+        })
+        .into()
+    }
+
+    pub fn array(field: &str, op: ArrayOp, ids: &[u32]) -> Self {
+        F::Array(ArrayFilter {
+            field: field.to_string(),
+            op: op as i32,
+            ids: ids.to_vec(),
+            length: 0,
+        })
+        .into()
+    }
+
+    /// compare the number of ids in the column, `op` must be one of the length operators
+    pub fn length(field: &str, op: ArrayOp, length: u32) -> Self {
+        F::Array(ArrayFilter {
+            field: field.to_string(),
+            op: op as i32,
+            ids: vec![],
+            length,
+        })
+        .into()
+    }
+
+    pub fn gender(gender: Gender) -> Self {
+        F::Gender(gender as i32).into()
+    }
+
+    pub fn null(field: &str, not_null: bool) -> Self {
+        F::Null(NullCheck {
+            field: field.to_string(),
+            not_null,
+        })
+        .into()
+    }
+
+    /// append the parenthesized sql of the filter, values are bound as parameters and fields are
+    /// checked against the columns of `user_stats`
+    #[allow(clippy::result_large_err)]
+    pub(super) fn push_to(&self, builder: &mut Builder) -> Result<(), Status> {
+        let Some(filter) = &self.filter else {
+            return Err(Status::invalid_argument("Filter must not be empty"));
+        };
+
+        builder.push("(");
+        match filter {
+            F::And(list) => push_list(builder, &list.filters, " AND ", "TRUE")?,
+            F::Or(list) => push_list(builder, &list.filters, " OR ", "FALSE")?,
+            F::Not(filter) => {
+                builder.push("NOT ");
+                filter.push_to(builder)?;
+            }
+            F::Time(range) => {
+                let column = column(&range.field, TIMESTAMP_COLUMNS, "timestamp")?;
+                push_time_range(builder, column, range.lower.as_ref(), range.upper.as_ref())?;
+            }
+            F::Array(array) => {
+                let column = column(&array.field, ID_COLUMNS, "id")?;
+                push_array(builder, column, array.op(), &array.ids, array.length);
+            }
+            F::Gender(gender) => {
+                let gender = Gender::try_from(*gender)
+                    .map_err(|_| Status::invalid_argument(format!("Unknown gender: {}", gender)))?;
+                builder.push("gender = ").push_bind(DbGender::from(gender));
+            }
+            F::Null(check) => {
+                let column = column(&check.field, NULLABLE_COLUMNS, "nullable")?;
+                let op = if check.not_null {
+                    " IS NOT NULL"
+                } else {
+                    " IS NULL"
+                };
+                builder.push(column).push(op);
+            }
+        }
+        builder.push(")");
+        Ok(())
+    }
+}
+
+impl ops::Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Self::Output {
+        F::Not(Box::new(self)).into()
+    }
+}
+
+impl From<F> for Filter {
+    fn from(filter: F) -> Self {
+        Filter {
+            filter: Some(filter),
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn push_list(
+    builder: &mut Builder,
+    filters: &[Filter],
+    sep: &str,
+    empty: &str,
+) -> Result<(), Status> {
+    if filters.is_empty() {
+        builder.push(empty);
+        return Ok(());
+    }
+    for (i, filter) in filters.iter().enumerate() {
+        if i > 0 {
+            builder.push(sep);
+        }
+        filter.push_to(builder)?;
+    }
+    Ok(())
+}
+
+/// the column within the range, an open range on both ends matches everything
+#[allow(clippy::result_large_err)]
+pub(super) fn push_time_range(
+    builder: &mut Builder,
+    column: &'static str,
+    lower: Option<&Timestamp>,
+    upper: Option<&Timestamp>,
+) -> Result<(), Status> {
+    let lower = lower.map(ts_to_utc).transpose()?;
+    let upper = upper.map(ts_to_utc).transpose()?;
+    match (lower, upper) {
+        (Some(lower), Some(upper)) => {
+            builder.push(column).push(" BETWEEN ").push_bind(lower);
+            builder.push(" AND ").push_bind(upper);
+        }
+        (Some(lower), None) => {
+            builder.push(column).push(" >= ").push_bind(lower);
+        }
+        (None, Some(upper)) => {
+            builder.push(column).push(" <= ").push_bind(upper);
+        }
+        (None, None) => {
+            builder.push("TRUE");
+        }
+    }
+    Ok(())
+}
+
+/// match the ids against the array column. Containment and overlap are written as
+/// `column @> ids` and `column && ids` so the GIN indexes on the columns can be used, the
+/// negated and length operators always scan
+pub(super) fn push_array(
+    builder: &mut Builder,
+    column: &'static str,
+    op: ArrayOp,
+    ids: &[u32],
+    length: u32,
+) {
+    let cmp = match op {
+        ArrayOp::LengthGt => Some(" > "),
+        ArrayOp::LengthLt => Some(" < "),
+        ArrayOp::LengthEq => Some(" = "),
+        _ => None,
+    };
+    if let Some(cmp) = cmp {
+        // cardinality of a null array is null, which is treated as empty
+        builder
+            .push("coalesce(cardinality(")
+            .push(column)
+            .push("), 0)");
+        builder.push(cmp).push_bind(length as i32);
+        return;
+    }
+
+    if ids.is_empty() {
+        builder.push(match op {
+            ArrayOp::ContainsAny => "FALSE",
+            _ => "TRUE",
+        });
+        return;
+    }
+
+    let ids: Vec<i32> = ids.iter().map(|id| *id as i32).collect();
+    match op {
+        ArrayOp::ContainsAny => {
+            builder.push(column).push(" && ").push_bind(ids);
+        }
+        ArrayOp::ContainsNone => {
+            builder.push("(").push(column).push(" IS NULL OR NOT ");
+            builder.push(column).push(" && ").push_bind(ids).push(")");
+        }
+        _ => {
+            builder.push(column).push(" @> ").push_bind(ids);
+        }
+    }
+}
+
+fn utc_to_ts(dt: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: dt.timestamp(),
+        nanos: dt.timestamp_subsec_nanos() as i32,
+    }
+}
@@ -0,0 +1,562 @@
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Deserializer};
+use sqlx::PgConnection;
+use tonic::{Response, Status};
+use tracing::info;
+
+use super::profile::PROFILE_COLUMNS;
+use crate::{
+    pb::{DataFormat, ImportRequest, ImportResponse},
+    ServiceResult, UserStatsService,
+};
+
+/// a row of an imported file, columns missing from the file are None
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ImportRow {
+    email: String,
+    name: String,
+    #[serde(default)]
+    gender: Option<String>,
+    #[serde(default)]
+    locale: Option<String>,
+    #[serde(default)]
+    phone: Option<String>,
+    #[serde(default)]
+    device_id: Option<String>,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    last_visited_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    last_watched_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    last_email_notification: Option<DateTime<Utc>>,
+    #[serde(default)]
+    last_in_app_notification: Option<DateTime<Utc>>,
+    #[serde(default)]
+    last_sms_notification: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "ids")]
+    recent_watched: Option<Vec<i32>>,
+    #[serde(default, deserialize_with = "ids")]
+    viewed_but_not_started: Option<Vec<i32>>,
+    #[serde(default, deserialize_with = "ids")]
+    started_but_not_finished: Option<Vec<i32>>,
+    #[serde(default, deserialize_with = "ids")]
+    finished: Option<Vec<i32>>,
+}
+
+/// content ids are a json array, or in csv a single id or a list like `{1,2,3}`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Ids {
+    List(Vec<i32>),
+    Single(i32),
+    Text(String),
+}
+
+/// number of rows copied into the database at once
+const IMPORT_BATCH_ROWS: usize = 1000;
+
+/// turns the uploaded chunks into rows as they arrive, a row may span several chunks
+struct RowParser {
+    format: DataFormat,
+    /// the trailing part of the upload which doesn't make a complete row yet
+    pending: Vec<u8>,
+    /// bytes of `pending` scanned for the end of a row so far, they hold no complete row
+    scanned: usize,
+    /// whether the scanned csv data ends within a quoted field
+    quoted: bool,
+    /// field names of a csv file, read from its first record
+    header: Option<csv::StringRecord>,
+    /// number of csv records or ndjson lines parsed so far, rows are numbered from 1
+    rows: usize,
+}
+
+impl UserStatsService {
+    /// parse and copy the rows in batches as the chunks arrive, then update the existing users
+    /// and insert the new ones in the same transaction, so that a bad file is rejected as a
+    /// whole
+    pub async fn import(
+        &self,
+        mut stream: impl Stream<Item = Result<ImportRequest, Status>> + Send + Unpin,
+    ) -> ServiceResult<ImportResponse> {
+        let columns = PROFILE_COLUMNS.join(", ");
+        let mut tx = self.inner.pool.begin().await.map_err(internal)?;
+        sqlx::query(&format!(
+            "CREATE TEMP TABLE user_stats_import ON COMMIT DROP AS \
+             SELECT {} FROM user_stats WITH NO DATA",
+            columns
+        ))
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?;
+
+        let mut parser: Option<RowParser> = None;
+        let mut batch = Vec::with_capacity(IMPORT_BATCH_ROWS);
+        let mut total = 0;
+        while let Some(req) = stream.next().await {
+            let req = req?;
+            let parser = match &mut parser {
+                Some(parser) => parser,
+                None => parser.insert(RowParser::try_new(req.format())?),
+            };
+            batch.extend(parser.feed(&req.data)?);
+            if batch.len() >= IMPORT_BATCH_ROWS {
+                total += batch.len();
+                copy_rows(&mut tx, &batch).await.map_err(internal)?;
+                batch.clear();
+            }
+        }
+        if let Some(parser) = parser {
+            batch.extend(parser.finish()?);
+        }
+        if !batch.is_empty() {
+            total += batch.len();
+            copy_rows(&mut tx, &batch).await.map_err(internal)?;
+        }
+
+        let duplicate: Option<(String,)> = sqlx::query_as(
+            "SELECT email FROM user_stats_import GROUP BY email HAVING count(*) > 1 LIMIT 1",
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(internal)?;
+        if let Some((email,)) = duplicate {
+            return Err(Status::invalid_argument(format!(
+                "Invalid import: duplicate email {}",
+                email
+            )));
+        }
+
+        let updated = sqlx::query(&update_sql())
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        let inserted = sqlx::query(&insert_sql())
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        tx.commit().await.map_err(internal)?;
+
+        let ret = ImportResponse {
+            inserted: inserted.rows_affected(),
+            updated: updated.rows_affected(),
+        };
+        info!(
+            "Imported {} users, {} inserted and {} updated",
+            total, ret.inserted, ret.updated
+        );
+        Ok(Response::new(ret))
+    }
+}
+
+async fn copy_rows(conn: &mut PgConnection, rows: &[ImportRow]) -> Result<(), sqlx::Error> {
+    let mut copy = conn
+        .copy_in_raw(&format!(
+            "COPY user_stats_import ({}) FROM STDIN WITH (FORMAT csv)",
+            PROFILE_COLUMNS.join(", ")
+        ))
+        .await?;
+    copy.send(to_csv(rows)).await?;
+    copy.finish().await?;
+    Ok(())
+}
+
+impl RowParser {
+    #[allow(clippy::result_large_err)]
+    fn try_new(format: DataFormat) -> Result<Self, Status> {
+        if format == DataFormat::Parquet {
+            return Err(Status::invalid_argument(
+                "Parquet files can't be imported, use csv or ndjson",
+            ));
+        }
+        Ok(Self {
+            format,
+            pending: Vec::new(),
+            scanned: 0,
+            quoted: false,
+            header: None,
+            rows: 0,
+        })
+    }
+
+    /// the complete rows the upload has got with the chunk
+    #[allow(clippy::result_large_err)]
+    fn feed(&mut self, data: &[u8]) -> Result<Vec<ImportRow>, Status> {
+        self.pending.extend_from_slice(data);
+        match self.complete_len() {
+            Some(len) => {
+                let data: Vec<u8> = self.pending.drain(..len).collect();
+                self.parse(&data)
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    /// the rows left once the upload is complete
+    #[allow(clippy::result_large_err)]
+    fn finish(mut self) -> Result<Vec<ImportRow>, Status> {
+        let data = std::mem::take(&mut self.pending);
+        self.parse(&data)
+    }
+
+    /// length of the complete rows at the start of the pending data; a csv row ends with a
+    /// newline outside of quotes, quotes within a quoted field are doubled so the parity
+    /// still holds. Only the bytes added since the last call are scanned
+    fn complete_len(&mut self) -> Option<usize> {
+        let start = self.scanned;
+        self.scanned = self.pending.len();
+        let new = &self.pending[start..];
+        let len = match self.format {
+            DataFormat::Csv => {
+                let mut len = None;
+                for (i, b) in new.iter().enumerate() {
+                    match b {
+                        b'"' => self.quoted = !self.quoted,
+                        b'\n' if !self.quoted => len = Some(i + 1),
+                        _ => {}
+                    }
+                }
+                len
+            }
+            _ => new.iter().rposition(|b| *b == b'\n').map(|i| i + 1),
+        }
+        .map(|len| start + len)?;
+        // the complete rows are drained, a row ends outside of quotes
+        self.scanned -= len;
+        Some(len)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn parse(&mut self, data: &[u8]) -> Result<Vec<ImportRow>, Status> {
+        let mut rows = Vec::new();
+        match self.format {
+            DataFormat::Csv => {
+                let mut reader = csv::ReaderBuilder::new()
+                    .has_headers(false)
+                    .from_reader(data);
+                for record in reader.records() {
+                    let record = record.map_err(|e| invalid(self.rows + 1, &e))?;
+                    let Some(header) = &self.header else {
+                        self.header = Some(record);
+                        continue;
+                    };
+
+                    self.rows += 1;
+                    if record.len() != header.len() {
+                        let reason = format!(
+                            "found {} fields, but the header has {}",
+                            record.len(),
+                            header.len()
+                        );
+                        return Err(invalid(self.rows, &reason));
+                    }
+                    let row: ImportRow = record
+                        .deserialize(Some(header))
+                        .map_err(|e| invalid(self.rows, &e))?;
+                    validate(self.rows, &row)?;
+                    rows.push(row);
+                }
+            }
+            _ => {
+                for line in data.split_inclusive(|b| *b == b'\n') {
+                    self.rows += 1;
+                    if line.trim_ascii().is_empty() {
+                        continue;
+                    }
+                    let row: ImportRow =
+                        serde_json::from_slice(line).map_err(|e| invalid(self.rows, &e))?;
+                    validate(self.rows, &row)?;
+                    rows.push(row);
+                }
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// check the row before anything is written; duplicate emails are checked once every row
+/// has been copied
+#[allow(clippy::result_large_err)]
+fn validate(n: usize, row: &ImportRow) -> Result<(), Status> {
+    let reason = if !row.email.contains('@') || row.email.len() > 128 {
+        Some(format!("invalid email {:?}", row.email))
+    } else if row.name.is_empty() || row.name.len() > 64 {
+        Some(format!("invalid name {:?}", row.name))
+    } else if let Some(gender) = row
+        .gender
+        .as_ref()
+        .filter(|g| !["female", "male", "unknown"].contains(&g.as_str()))
+    {
+        Some(format!("invalid gender {:?}", gender))
+    } else if let Some(locale) = row.locale.as_ref().filter(|l| !is_locale(l)) {
+        Some(format!("invalid locale {:?}", locale))
+    } else if row.phone.as_ref().is_some_and(|p| p.len() > 32) {
+        Some("phone is too long".to_string())
+    } else if row.device_id.as_ref().is_some_and(|d| d.len() > 64) {
+        Some("device_id is too long".to_string())
+    } else {
+        None
+    };
+
+    match reason {
+        Some(reason) => Err(invalid(n, &reason)),
+        None => Ok(()),
+    }
+}
+
+fn invalid(n: usize, e: &dyn std::fmt::Display) -> Status {
+    Status::invalid_argument(format!("Invalid row {}: {}", n, e))
+}
+
+fn internal(e: sqlx::Error) -> Status {
+    Status::internal(format!("Failed to import users: {}", e))
+}
+
+/// the rows in the column order of `PROFILE_COLUMNS`, missing values are written as NULL
+fn to_csv(rows: &[ImportRow]) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        let ts = |ts: &Option<DateTime<Utc>>| ts.map(|ts| ts.to_rfc3339()).unwrap_or_default();
+        let ids = |ids: &Option<Vec<i32>>| ids.as_deref().map(format_ids).unwrap_or_default();
+        let record = [
+            row.email.clone(),
+            row.name.clone(),
+            row.gender.clone().unwrap_or_default(),
+            row.locale.clone().unwrap_or_default(),
+            row.phone.clone().unwrap_or_default(),
+            row.device_id.clone().unwrap_or_default(),
+            ts(&row.created_at),
+            ts(&row.last_visited_at),
+            ts(&row.last_watched_at),
+            ts(&row.last_email_notification),
+            ts(&row.last_in_app_notification),
+            ts(&row.last_sms_notification),
+            ids(&row.recent_watched),
+            ids(&row.viewed_but_not_started),
+            ids(&row.started_but_not_finished),
+            ids(&row.finished),
+        ];
+        writer
+            .write_record(&record)
+            .expect("writing to a vec never fails");
+    }
+    writer.into_inner().expect("writing to a vec never fails")
+}
+
+/// columns missing from the import keep their value, name is always imported
+fn update_sql() -> String {
+    let set = PROFILE_COLUMNS
+        .iter()
+        .filter(|c| **c != "email")
+        .map(|c| match *c {
+            "name" => "name = i.name".to_string(),
+            c => format!("{0} = coalesce(i.{0}, u.{0})", c),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "UPDATE user_stats u SET {} FROM user_stats_import i WHERE u.email = i.email",
+        set
+    )
+}
+
+/// the columns which can't be null get their usual defaults
+fn insert_sql() -> String {
+    let values = PROFILE_COLUMNS
+        .iter()
+        .map(|c| match *c {
+            "gender" => "coalesce(i.gender, 'unknown')".to_string(),
+            "locale" => "coalesce(i.locale, 'en')".to_string(),
+            "created_at" => "coalesce(i.created_at, now())".to_string(),
+            c => format!("i.{}", c),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    // users registered by a concurrent ingest since the update are left as they are
+    format!(
+        "INSERT INTO user_stats ({}) SELECT {} FROM user_stats_import i \
+         ON CONFLICT (email) DO NOTHING",
+        PROFILE_COLUMNS.join(", "),
+        values
+    )
+}
+
+/// content ids as a postgres array literal, e.g. `{1,2,3}`
+pub(super) fn format_ids(ids: &[i32]) -> String {
+    let ids: Vec<_> = ids.iter().map(|id| id.to_string()).collect();
+    format!("{{{}}}", ids.join(","))
+}
+
+fn ids<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<i32>>, D::Error> {
+    let ids = match Option::<Ids>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(Ids::List(ids)) => ids,
+        Some(Ids::Single(id)) => vec![id],
+        Some(Ids::Text(text)) => text
+            .trim_matches(|c| c == '{' || c == '}')
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| serde::de::Error::custom(format!("invalid content ids {:?}", text)))?,
+    };
+    Ok(Some(ids))
+}
+
+/// locales are tags like zh-CN
+fn is_locale(locale: &str) -> bool {
+    locale.len() <= 16
+        && !locale.is_empty()
+        && locale
+            .split('-')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use futures::stream;
+
+    use super::*;
+
+    fn request(format: DataFormat, data: &str) -> ImportRequest {
+        ImportRequest {
+            format: format as i32,
+            data: data.as_bytes().to_vec(),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn parse(format: DataFormat, data: &[u8]) -> Result<Vec<ImportRow>, Status> {
+        let mut parser = RowParser::try_new(format)?;
+        let mut rows = parser.feed(data)?;
+        rows.extend(parser.finish()?);
+        Ok(rows)
+    }
+
+    #[test]
+    fn csv_and_ndjson_rows_should_be_parsed() {
+        let csv = "email,name,gender,created_at,finished\n\
+                   alice@acme.org,Alice,female,2024-05-01T00:00:00Z,\"{1,2}\"\n\
+                   bob@acme.org,Bob,,,3\n";
+        let rows = parse(DataFormat::Csv, csv.as_bytes()).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].gender.as_deref(), Some("female"));
+        assert_eq!(rows[0].finished, Some(vec![1, 2]));
+        assert!(rows[1].gender.is_none());
+        assert!(rows[1].created_at.is_none());
+        assert_eq!(rows[1].finished, Some(vec![3]));
+
+        let ndjson = "{\"email\":\"alice@acme.org\",\"name\":\"Alice\",\"finished\":[1,2]}\n\n\
+                      {\"email\":\"bob@acme.org\",\"name\":\"Bob\",\"phone\":null}\n";
+        let rows = parse(DataFormat::Ndjson, ndjson.as_bytes()).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].finished, Some(vec![1, 2]));
+        assert!(rows[1].finished.is_none());
+
+        let err = parse(DataFormat::Csv, b"email,name,password\na@acme.org,A,x\n").unwrap_err();
+        assert!(err.message().starts_with("Invalid row 1"));
+
+        let err = parse(DataFormat::Parquet, b"").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn rows_split_across_chunks_should_be_parsed() {
+        let csv = "email,name\nalice@acme.org,\"Alice \"\"A\"\"\nLiddell\"\nbob@acme.org,Bob\n";
+        // the scan state is kept between chunks, so split the rows at every position
+        for size in 1..=csv.len() {
+            let mut parser = RowParser::try_new(DataFormat::Csv).unwrap();
+            let mut rows = vec![];
+            for chunk in csv.as_bytes().chunks(size) {
+                rows.extend(parser.feed(chunk).unwrap());
+            }
+            rows.extend(parser.finish().unwrap());
+            assert_eq!(rows.len(), 2);
+            assert_eq!(rows[0].name, "Alice \"A\"\nLiddell");
+            assert_eq!(rows[1].email, "bob@acme.org");
+        }
+    }
+
+    #[test]
+    fn invalid_rows_should_be_rejected() {
+        let cases = [
+            ("email,name\nalice,Alice\n", "invalid email"),
+            ("email,name\na@acme.org,\n", "invalid name"),
+            ("email,name,gender\na@acme.org,A,other\n", "invalid gender"),
+            ("email,name,locale\na@acme.org,A,zh_CN\n", "invalid locale"),
+            ("email,name\na@acme.org,A\nb@acme.org\n", "1 fields"),
+        ];
+        for (csv, reason) in cases {
+            let err = parse(DataFormat::Csv, csv.as_bytes()).unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument);
+            assert!(err.message().contains(reason), "{}", err.message());
+        }
+    }
+
+    #[tokio::test]
+    async fn import_should_insert_and_update_users() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let (email, name, finished): (String, String, Vec<i32>) =
+            sqlx::query_as("SELECT email, name, finished FROM user_stats LIMIT 1")
+                .fetch_one(&svc.pool)
+                .await?;
+
+        let csv = format!(
+            "email,name,locale\n{},Renamed,fr\nnew@acme.org,New,\n",
+            email
+        );
+        // split to make sure chunks are joined
+        let (a, b) = csv.split_at(10);
+        let reqs = vec![request(DataFormat::Csv, a), request(DataFormat::Csv, b)];
+        let ret = svc
+            .import(stream::iter(reqs.into_iter().map(Ok)))
+            .await?
+            .into_inner();
+        assert_eq!((ret.inserted, ret.updated), (1, 1));
+
+        let (new_name, locale, new_finished): (String, String, Vec<i32>) =
+            sqlx::query_as("SELECT name, locale, finished FROM user_stats WHERE email = $1")
+                .bind(&email)
+                .fetch_one(&svc.pool)
+                .await?;
+        assert_ne!(new_name, name);
+        assert_eq!(locale, "fr");
+        assert_eq!(new_finished, finished);
+
+        let (locale, gender): (String, String) = sqlx::query_as(
+            "SELECT locale, gender::text FROM user_stats WHERE email = 'new@acme.org'",
+        )
+        .fetch_one(&svc.pool)
+        .await?;
+        assert_eq!((locale.as_str(), gender.as_str()), ("en", "unknown"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn import_with_duplicate_emails_should_be_rejected() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let csv = "email,name\nnew@acme.org,A\nnew@acme.org,B\n";
+        let reqs = vec![request(DataFormat::Csv, csv)];
+        let err = svc
+            .import(stream::iter(reqs.into_iter().map(Ok)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        assert!(
+            err.message().contains("duplicate email"),
+            "{}",
+            err.message()
+        );
+
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT count(*) FROM user_stats WHERE email = 'new@acme.org'")
+                .fetch_one(&svc.pool)
+                .await?;
+        assert_eq!(count, 0);
+        Ok(())
+    }
+}
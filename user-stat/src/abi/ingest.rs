@@ -0,0 +1,331 @@
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use sqlx::PgConnection;
+use tonic::{Response, Status};
+use tracing::info;
+
+use super::{ts_to_utc, DbGender};
+use crate::{
+    pb::{user_event::Event, IngestResponse, UserEvent},
+    ServiceResult, UserStatsService,
+};
+
+/// move the content to the front of recent_watched, keeping at most $4 ids
+const PUSH_RECENT: &str =
+    "recent_watched = (array_prepend($3, array_remove(coalesce(recent_watched, '{}'), $3)))[1:$4]";
+
+impl UserStatsService {
+    pub async fn ingest(
+        &self,
+        mut stream: impl Stream<Item = Result<UserEvent, Status>> + Send + Unpin,
+    ) -> ServiceResult<IngestResponse> {
+        let batch_size = self.inner.config.ingest.batch_size.max(1);
+        let mut ret = IngestResponse::default();
+        let mut batch = Vec::with_capacity(batch_size);
+        while let Some(event) = stream.next().await {
+            batch.push(event?);
+            if batch.len() >= batch_size {
+                self.apply_events(&batch, &mut ret).await?;
+                batch.clear();
+            }
+        }
+        self.apply_events(&batch, &mut ret).await?;
+
+        info!("Ingested {} events, skipped {}", ret.applied, ret.skipped);
+        Ok(Response::new(ret))
+    }
+
+    /// apply the events in order in a single transaction, either all of them are applied or none
+    async fn apply_events(
+        &self,
+        events: &[UserEvent],
+        ret: &mut IngestResponse,
+    ) -> Result<(), Status> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        for event in events {
+            validate(event)?;
+        }
+
+        let recent = self.inner.config.ingest.recent_watched as i32;
+        let mut tx = self.inner.pool.begin().await.map_err(internal)?;
+        let (mut applied, mut skipped) = (0, 0);
+        for event in events {
+            if apply_event(&mut tx, event, recent)
+                .await
+                .map_err(internal)?
+            {
+                applied += 1;
+            } else {
+                skipped += 1;
+            }
+        }
+        tx.commit().await.map_err(internal)?;
+
+        ret.applied += applied;
+        ret.skipped += skipped;
+        Ok(())
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn validate(event: &UserEvent) -> Result<(), Status> {
+    if event.email.is_empty() {
+        return Err(Status::invalid_argument("Event email is required"));
+    }
+    match &event.event {
+        None => Err(Status::invalid_argument(format!(
+            "Event of {} is empty",
+            event.email
+        ))),
+        Some(Event::Notified(notified)) if notified.channel().column().is_none() => Err(
+            Status::invalid_argument(format!("Notified event of {} has no channel", event.email)),
+        ),
+        Some(_) => match &event.occurred_at {
+            Some(ts) => ts_to_utc(ts).map(|_| ()),
+            None => Ok(()),
+        },
+    }
+}
+
+/// update the row of the user, returns false if the user doesn't exist
+async fn apply_event(
+    conn: &mut PgConnection,
+    event: &UserEvent,
+    recent: i32,
+) -> Result<bool, sqlx::Error> {
+    // the timestamp has been validated already
+    let at: DateTime<Utc> = event
+        .occurred_at
+        .as_ref()
+        .and_then(|ts| ts_to_utc(ts).ok())
+        .unwrap_or_else(Utc::now);
+    let Some(e) = &event.event else {
+        return Ok(false);
+    };
+
+    let ret = match e {
+        Event::Registered(user) => {
+            let locale = if user.locale.is_empty() {
+                "en"
+            } else {
+                user.locale.as_str()
+            };
+            sqlx::query(
+                "INSERT INTO user_stats(email, created_at, name, gender, locale, phone, device_id) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7) \
+                 ON CONFLICT (email) DO UPDATE SET name = EXCLUDED.name, \
+                 gender = EXCLUDED.gender, locale = EXCLUDED.locale, \
+                 phone = coalesce(EXCLUDED.phone, user_stats.phone), \
+                 device_id = coalesce(EXCLUDED.device_id, user_stats.device_id)",
+            )
+            .bind(&event.email)
+            .bind(at)
+            .bind(&user.name)
+            .bind(DbGender::from(user.gender()))
+            .bind(locale)
+            .bind(&user.phone)
+            .bind(&user.device_id)
+            .execute(conn)
+            .await?
+        }
+        Event::Visited(_) => {
+            sqlx::query(
+                "UPDATE user_stats SET last_visited_at = greatest(last_visited_at, $2) \
+                 WHERE email = $1",
+            )
+            .bind(&event.email)
+            .bind(at)
+            .execute(conn)
+            .await?
+        }
+        // started or finished contents stay where they are
+        Event::Viewed(content) => {
+            sqlx::query(
+                "UPDATE user_stats SET last_visited_at = greatest(last_visited_at, $2), \
+                 viewed_but_not_started = CASE \
+                 WHEN $3 = ANY(started_but_not_finished) OR $3 = ANY(finished) \
+                 THEN viewed_but_not_started \
+                 ELSE array_append(array_remove(coalesce(viewed_but_not_started, '{}'), $3), $3) \
+                 END WHERE email = $1",
+            )
+            .bind(&event.email)
+            .bind(at)
+            .bind(content.content_id as i32)
+            .execute(conn)
+            .await?
+        }
+        // a finished content started again stays finished
+        Event::Started(content) => {
+            sqlx::query(&format!(
+                "UPDATE user_stats SET last_watched_at = greatest(last_watched_at, $2), {}, \
+                 viewed_but_not_started = array_remove(viewed_but_not_started, $3), \
+                 started_but_not_finished = CASE WHEN $3 = ANY(finished) \
+                 THEN started_but_not_finished \
+                 ELSE array_append(array_remove(coalesce(started_but_not_finished, '{{}}'), $3), $3) \
+                 END WHERE email = $1",
+                PUSH_RECENT
+            ))
+            .bind(&event.email)
+            .bind(at)
+            .bind(content.content_id as i32)
+            .bind(recent)
+            .execute(conn)
+            .await?
+        }
+        Event::Finished(content) => {
+            sqlx::query(&format!(
+                "UPDATE user_stats SET last_watched_at = greatest(last_watched_at, $2), {}, \
+                 viewed_but_not_started = array_remove(viewed_but_not_started, $3), \
+                 started_but_not_finished = array_remove(started_but_not_finished, $3), \
+                 finished = array_append(array_remove(coalesce(finished, '{{}}'), $3), $3) \
+                 WHERE email = $1",
+                PUSH_RECENT
+            ))
+            .bind(&event.email)
+            .bind(at)
+            .bind(content.content_id as i32)
+            .bind(recent)
+            .execute(conn)
+            .await?
+        }
+        Event::Notified(notified) => {
+            let Some(column) = notified.channel().column() else {
+                return Ok(false);
+            };
+            sqlx::query(&format!(
+                "UPDATE user_stats SET {0} = greatest({0}, $2) WHERE email = $1",
+                column
+            ))
+            .bind(&event.email)
+            .bind(at)
+            .execute(conn)
+            .await?
+        }
+    };
+    Ok(ret.rows_affected() > 0)
+}
+
+fn internal(e: sqlx::Error) -> Status {
+    Status::internal(format!("Failed to ingest events: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use futures::stream;
+    use prost_types::Timestamp;
+
+    use super::*;
+    use crate::pb::{ContentEvent, Gender, NotificationChannel, Notified, Registered, Visited};
+
+    fn event(email: &str, event: Event) -> UserEvent {
+        UserEvent {
+            email: email.to_string(),
+            occurred_at: None,
+            event: Some(event),
+        }
+    }
+
+    fn content(id: u32) -> ContentEvent {
+        ContentEvent { content_id: id }
+    }
+
+    #[tokio::test]
+    async fn ingest_should_update_user_stats() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let email = "ingest@acme.org";
+        let mut events = vec![
+            event(
+                email,
+                Event::Registered(Registered {
+                    name: "Alice".to_string(),
+                    gender: Gender::Female as i32,
+                    ..Default::default()
+                }),
+            ),
+            event(email, Event::Visited(Visited {})),
+            event(email, Event::Viewed(content(1))),
+            event(email, Event::Viewed(content(2))),
+            event(email, Event::Started(content(1))),
+            event(email, Event::Finished(content(1))),
+            event(
+                email,
+                Event::Notified(Notified {
+                    channel: NotificationChannel::Email as i32,
+                }),
+            ),
+            event("nobody@acme.org", Event::Visited(Visited {})),
+        ];
+        // more than recent_watched contents
+        events.extend((100..112).map(|id| event(email, Event::Started(content(id)))));
+
+        let ret = svc
+            .ingest(stream::iter(events.into_iter().map(Ok)))
+            .await?
+            .into_inner();
+        assert_eq!(ret.applied, 19);
+        assert_eq!(ret.skipped, 1);
+
+        #[allow(clippy::type_complexity)]
+        let (viewed, started, finished, recent, visited, notified): (
+            Vec<i32>,
+            Vec<i32>,
+            Vec<i32>,
+            Vec<i32>,
+            Option<DateTime<Utc>>,
+            Option<DateTime<Utc>>,
+        ) = sqlx::query_as(
+            "SELECT viewed_but_not_started, started_but_not_finished, finished, recent_watched, \
+             last_visited_at, last_email_notification FROM user_stats WHERE email = $1",
+        )
+        .bind(email)
+        .fetch_one(&svc.pool)
+        .await?;
+        assert_eq!(viewed, [2]);
+        assert_eq!(started, (100..112).collect::<Vec<_>>());
+        assert_eq!(finished, [1]);
+        assert_eq!(recent, (102..112).rev().collect::<Vec<_>>());
+        assert!(visited.is_some());
+        assert!(notified.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_event_should_reject_its_batch() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let events = vec![
+            event("ingest@acme.org", Event::Registered(Registered::default())),
+            UserEvent {
+                email: "ingest@acme.org".to_string(),
+                ..Default::default()
+            },
+        ];
+        let err = svc
+            .ingest(stream::iter(events.into_iter().map(Ok)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT count(*) FROM user_stats WHERE email = 'ingest@acme.org'")
+                .fetch_one(&svc.pool)
+                .await?;
+        assert_eq!(count, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn event_with_invalid_timestamp_should_be_rejected() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let mut visited = event("ingest@acme.org", Event::Visited(Visited::default()));
+        visited.occurred_at = Some(Timestamp {
+            seconds: 0,
+            nanos: -1,
+        });
+        let err = svc.ingest(stream::iter([Ok(visited)])).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+}
@@ -1,68 +1,176 @@
-use std::fmt;
+mod aggregate;
+mod export;
+mod filter;
+mod import;
+mod ingest;
+mod page;
+mod profile;
+mod sql;
+
+pub(crate) use profile::{DbIds, DbTimestamp};
+
+use std::pin::Pin;
 
 use chrono::{DateTime, TimeZone, Utc};
+use futures::{Stream, StreamExt};
 use itertools::Itertools;
 use prost_types::Timestamp;
+use sqlx::{postgres::PgRow, FromRow, Postgres, QueryBuilder};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Response, Status};
 use tracing::info;
 
 use crate::{
-    pb::{QueryRequest, QueryRequestBuilder, RawQueryRequest, TimeQuery, User},
+    pb::{
+        Gender, MarkNotifiedRequest, MarkNotifiedResponse, NotificationChannel, QueryRequest,
+        QueryRequestBuilder, RawQueryRequest, TimeQuery,
+    },
     ResponseStream, ServiceResult, UserStatsService,
 };
 
+/// rows buffered ahead of the client, once full the cursor waits for the client to catch up
+const CHANNEL_SIZE: usize = 128;
+
+/// columns selected for `User`, in the same order as the message fields
+const USER_COLUMNS: &str =
+    "email, name, started_but_not_finished, phone, device_id, locale, gender";
+
+/// timestamp columns of `user_stats` a query may filter on
+const TIMESTAMP_COLUMNS: &[&str] = &[
+    "created_at",
+    "last_visited_at",
+    "last_watched_at",
+    "last_email_notification",
+    "last_in_app_notification",
+    "last_sms_notification",
+];
+
+/// content id array columns of `user_stats` a query may filter on
+const ID_COLUMNS: &[&str] = &[
+    "recent_watched",
+    "viewed_but_not_started",
+    "started_but_not_finished",
+    "finished",
+];
+
+/// gender as stored in the `gender` postgres enum, decoded into `User.gender`
+#[derive(Debug, Clone, Copy, sqlx::Type)]
+#[sqlx(type_name = "gender", rename_all = "lowercase")]
+pub(crate) enum DbGender {
+    Female,
+    Male,
+    Unknown,
+}
+
 impl UserStatsService {
     pub async fn query(&self, query: QueryRequest) -> ServiceResult<ResponseStream> {
-        let sql = query.to_string();
+        let builder = query.to_query()?;
+        info!("Generated SQL: {}", builder.sql());
 
-        self.raw_query(RawQueryRequest { query: sql }).await
+        Ok(Response::new(self.fetch_rows(builder)))
     }
 
     pub async fn raw_query(&self, req: RawQueryRequest) -> ServiceResult<ResponseStream> {
-        // TODO: query must only return email and name, so we should use sqlparser to parse the query
-        let Ok(ret) = sqlx::query_as::<_, User>(&req.query)
-            .fetch_all(&self.inner.pool)
-            .await
-        else {
-            return Err(Status::internal(format!(
-                "Failed to fetch data with query: {}",
-                req.query
-            )));
-        };
+        sql::validate(&req.query)?;
 
-        Ok(Response::new(Box::pin(futures::stream::iter(
-            ret.into_iter().map(Ok),
-        ))))
+        Ok(Response::new(self.fetch_rows(QueryBuilder::new(req.query))))
     }
-}
 
-impl fmt::Display for QueryRequest {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // generate sql based on query
-        let mut sql = "SELECT email, name FROM user_stats WHERE ".to_string();
+    /// stream the rows of the query as postgres returns them. Rows are read from the cursor
+    /// only as fast as the client consumes them, and the query is dropped, releasing its
+    /// connection, as soon as the client goes away
+    fn fetch_rows<T>(
+        &self,
+        mut builder: QueryBuilder<'static, Postgres>,
+    ) -> Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'static,
+    {
+        let pool = self.inner.pool.clone();
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        tokio::spawn(async move {
+            let mut rows = builder.build_query_as::<T>().fetch(&pool);
+            loop {
+                let row = tokio::select! {
+                    row = rows.next() => row,
+                    _ = tx.closed() => break,
+                };
+                let Some(row) = row else {
+                    break;
+                };
+                let row = row.map_err(|e| Status::internal(format!("Failed to fetch data: {}", e)));
+                let failed = row.is_err();
+                if tx.send(row).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
 
-        let time_conditions = self
-            .timestamps
-            .iter()
-            .map(|(k, v)| timestamp_query(k, v.lower.as_ref(), v.upper.as_ref()))
-            .join(" AND ");
+        Box::pin(ReceiverStream::new(rx))
+    }
+}
 
-        sql.push_str(&time_conditions);
+impl UserStatsService {
+    pub async fn mark_notified(
+        &self,
+        req: MarkNotifiedRequest,
+    ) -> ServiceResult<MarkNotifiedResponse> {
+        let Some(column) = req.channel().column() else {
+            return Err(Status::invalid_argument("channel is required"));
+        };
+        let notified_at = match &req.notified_at {
+            Some(ts) => ts_to_utc(ts)?,
+            None => Utc::now(),
+        };
 
-        let id_conditions = self
-            .ids
-            .iter()
-            .map(|(k, v)| ids_query(k, &v.ids))
-            .join(" AND ");
+        let sql = format!(
+            "UPDATE user_stats SET {} = $2 WHERE email = ANY($1)",
+            column
+        );
+        let ret = sqlx::query(&sql)
+            .bind(&req.emails)
+            .bind(notified_at)
+            .execute(&self.inner.pool)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to mark users notified: {}", e)))?;
 
-        if !id_conditions.is_empty() {
-            sql.push_str(" AND ");
-            sql.push_str(&id_conditions);
+        Ok(Response::new(MarkNotifiedResponse {
+            count: ret.rows_affected(),
+        }))
+    }
+}
+
+impl NotificationChannel {
+    /// column keeping the last time a user has been notified through the channel
+    pub fn column(&self) -> Option<&'static str> {
+        match self {
+            NotificationChannel::Email => Some("last_email_notification"),
+            NotificationChannel::Sms => Some("last_sms_notification"),
+            NotificationChannel::InApp => Some("last_in_app_notification"),
+            NotificationChannel::Unspecified => None,
         }
+    }
+}
 
-        info!("Generated SQL: {}", sql);
+impl From<Gender> for DbGender {
+    fn from(gender: Gender) -> Self {
+        match gender {
+            Gender::Female => DbGender::Female,
+            Gender::Male => DbGender::Male,
+            Gender::Unknown => DbGender::Unknown,
+        }
+    }
+}
 
-        write!(f, "{}", sql)
+impl From<DbGender> for i32 {
+    fn from(gender: DbGender) -> Self {
+        let gender = match gender {
+            DbGender::Female => Gender::Female,
+            DbGender::Male => Gender::Male,
+            DbGender::Unknown => Gender::Unknown,
+        };
+        gender as i32
     }
 }
 
@@ -79,6 +187,7 @@ impl QueryRequest {
         let tq = TimeQuery {
             lower: Some(ts),
             upper: Some(ts1),
+            include_null: false,
         };
 
         QueryRequestBuilder::default()
@@ -86,66 +195,332 @@ impl QueryRequest {
             .build()
             .expect("Failed to build query request")
     }
-}
 
-fn ids_query(name: &str, ids: &[u32]) -> String {
-    if ids.is_empty() {
-        return "TRUE".to_string();
+    /// build the parameterized sql of the query, every key must be a column of `user_stats` of
+    /// the matching kind
+    #[allow(clippy::result_large_err)]
+    pub fn to_query(&self) -> Result<QueryBuilder<'static, Postgres>, Status> {
+        self.select(USER_COLUMNS)
     }
 
-    format!("array{:?} <@ {}", ids, name)
-}
-
-fn timestamp_query(name: &str, lower: Option<&Timestamp>, upper: Option<&Timestamp>) -> String {
-    if lower.is_none() && upper.is_none() {
-        return "TRUE".to_string();
+    /// the paged sql of the query selecting the given columns
+    #[allow(clippy::result_large_err)]
+    fn select(&self, columns: &str) -> Result<QueryBuilder<'static, Postgres>, Status> {
+        let mut builder =
+            QueryBuilder::new(format!("SELECT {} FROM user_stats WHERE TRUE", columns));
+        self.push_conditions(&mut builder)?;
+        page::push_page(&mut builder, self.page_size, &self.page_token)?;
+        Ok(builder)
     }
 
-    if lower.is_none() {
-        let upper = ts_to_utc(upper.unwrap());
-        return format!("{} <= '{}'", name, upper.to_rfc3339());
-    }
+    /// append the conditions of the query to a sql ending with a WHERE clause, pagination is
+    /// left to the caller
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn push_conditions(
+        &self,
+        builder: &mut QueryBuilder<'static, Postgres>,
+    ) -> Result<(), Status> {
+        // keys are sorted to generate the same sql for the same query
+        for (name, tq) in self.timestamps.iter().sorted_by_key(|(k, _)| *k) {
+            let column = column(name, TIMESTAMP_COLUMNS, "timestamp")?;
+            let (lower, upper) = (tq.lower.as_ref(), tq.upper.as_ref());
+            if lower.is_none() && upper.is_none() {
+                continue;
+            }
+
+            builder.push(" AND ");
+            if tq.include_null {
+                builder.push("(");
+            }
+            filter::push_time_range(builder, column, lower, upper)?;
+            if tq.include_null {
+                builder.push(" OR ").push(column).push(" IS NULL)");
+            }
+        }
+
+        for (name, iq) in self.ids.iter().sorted_by_key(|(k, _)| *k) {
+            let column = column(name, ID_COLUMNS, "id")?;
+            builder.push(" AND ");
+            filter::push_array(builder, column, iq.op(), &iq.ids, iq.length);
+        }
 
-    if upper.is_none() {
-        let lower = ts_to_utc(lower.unwrap());
-        return format!("{} >= '{}'", name, lower.to_rfc3339());
+        if let Some(filter) = &self.filter {
+            builder.push(" AND ");
+            filter.push_to(builder)?;
+        }
+
+        Ok(())
     }
+}
 
-    format!(
-        "{} BETWEEN '{}' AND '{}'",
-        name,
-        ts_to_utc(lower.unwrap()).to_rfc3339(),
-        ts_to_utc(upper.unwrap()).to_rfc3339()
-    )
+/// the column of the given kind with the name, so that only known columns end up in the sql
+#[allow(clippy::result_large_err)]
+fn column(name: &str, columns: &[&'static str], kind: &str) -> Result<&'static str, Status> {
+    columns
+        .iter()
+        .find(|c| **c == name)
+        .copied()
+        .ok_or_else(|| {
+            Status::invalid_argument(format!(
+                "Unknown {} field: {:?}, expected one of {}",
+                kind,
+                name,
+                columns.join(", ")
+            ))
+        })
 }
 
-fn ts_to_utc(ts: &Timestamp) -> DateTime<Utc> {
-    Utc.timestamp_opt(ts.seconds, ts.nanos as _).unwrap()
+/// a client given timestamp, nanos must be within a second as protobuf requires
+#[allow(clippy::result_large_err)]
+fn ts_to_utc(ts: &Timestamp) -> Result<DateTime<Utc>, Status> {
+    u32::try_from(ts.nanos)
+        .ok()
+        .filter(|nanos| *nanos < 1_000_000_000)
+        .and_then(|nanos| Utc.timestamp_opt(ts.seconds, nanos).single())
+        .ok_or_else(|| {
+            Status::invalid_argument(format!("Invalid timestamp: {}s {}ns", ts.seconds, ts.nanos))
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
-    use futures::StreamExt;
 
     use super::*;
     use crate::{
-        pb::QueryRequestBuilder,
+        pb::{ArrayOp, Filter, IdQuery, QueryRequestBuilder},
         test_utils::{id, tq},
     };
 
     #[test]
-    fn query_request_to_string_should_work() {
+    fn query_request_to_query_should_work() {
         let d1 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
         let d2 = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
-        let query = QueryRequest::new_with_dt("created_at", d1, d2);
-        let sql = query.to_string();
+        let mut query = QueryRequest::new_with_dt("created_at", d1, d2);
+        query.ids.insert("finished".to_string(), id(&[1, 2]));
+        let builder = query.to_query().unwrap();
         assert_eq!(
-            sql,
-            "SELECT email, name FROM user_stats WHERE created_at BETWEEN '2024-01-01T00:00:00+00:00' AND '2024-01-02T00:00:00+00:00'"
+            builder.sql(),
+            "SELECT email, name, started_but_not_finished, phone, device_id, locale, gender FROM user_stats WHERE TRUE AND created_at BETWEEN $1 AND $2 AND finished @> $3"
         );
     }
 
+    #[test]
+    fn query_request_with_id_ops_should_work() {
+        let any = IdQuery {
+            ids: vec![1, 2],
+            op: ArrayOp::ContainsAny as i32,
+            length: 0,
+        };
+        let longer = IdQuery {
+            ids: vec![],
+            op: ArrayOp::LengthGt as i32,
+            length: 10,
+        };
+        let query = QueryRequestBuilder::default()
+            .id(("recent_watched".to_string(), any))
+            .id(("finished".to_string(), longer))
+            .build()
+            .unwrap();
+        assert_eq!(
+            query.to_query().unwrap().sql(),
+            "SELECT email, name, started_but_not_finished, phone, device_id, locale, gender FROM user_stats WHERE TRUE AND coalesce(cardinality(finished), 0) > $1 AND recent_watched && $2"
+        );
+    }
+
+    #[test]
+    fn query_request_with_filter_should_work() {
+        let week_ago = Utc::now() - chrono::Duration::days(7);
+        let filter = Filter::and([
+            Filter::or([
+                Filter::time("last_visited_at", Some(week_ago), None),
+                Filter::null("started_but_not_finished", true),
+            ]),
+            !Filter::gender(Gender::Male),
+            Filter::array("finished", ArrayOp::ContainsNone, &[1, 2]),
+            Filter::array("recent_watched", ArrayOp::ContainsAny, &[3]),
+            Filter::or([]),
+        ]);
+        let query = QueryRequestBuilder::default()
+            .filter(filter)
+            .build()
+            .unwrap();
+        assert_eq!(
+            query.to_query().unwrap().sql(),
+            "SELECT email, name, started_but_not_finished, phone, device_id, locale, gender FROM user_stats WHERE TRUE AND \
+             (((last_visited_at >= $1) OR (started_but_not_finished IS NOT NULL)) AND (NOT (gender = $2)) AND \
+             ((finished IS NULL OR NOT finished && $3)) AND (recent_watched && $4) AND (FALSE))"
+        );
+
+        let query = QueryRequestBuilder::default()
+            .filter(Filter::null("email", false))
+            .build()
+            .unwrap();
+        let Err(err) = query.to_query() else {
+            panic!("query should be rejected");
+        };
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let query = QueryRequestBuilder::default()
+            .filter(!Filter::default())
+            .build()
+            .unwrap();
+        assert!(query.to_query().is_err());
+    }
+
+    #[tokio::test]
+    async fn query_with_filter_should_match_segment() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let since = Utc.with_ymd_and_hms(2024, 4, 25, 0, 0, 0).unwrap();
+        let (expected,): (i64,) = sqlx::query_as(
+            "SELECT count(*) FROM user_stats WHERE last_visited_at >= $1 \
+             OR cardinality(started_but_not_finished) > 0",
+        )
+        .bind(since)
+        .fetch_one(&svc.pool)
+        .await?;
+
+        // visited since, or has unfinished content, generated ids are within 300000..400000
+        let unfinished: Vec<u32> = (300000..400000).collect();
+        let filter = Filter::or([
+            Filter::time("last_visited_at", Some(since), None),
+            Filter::array(
+                "started_but_not_finished",
+                ArrayOp::ContainsAny,
+                &unfinished,
+            ),
+        ]);
+        let query = QueryRequestBuilder::default().filter(filter).build()?;
+        let ret = svc
+            .query(query)
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+        assert!(expected > 0);
+        assert_eq!(ret.len() as i64, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn query_with_length_should_work() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let (expected,): (i64,) =
+            sqlx::query_as("SELECT count(*) FROM user_stats WHERE array_length(finished, 1) > 10")
+                .fetch_one(&svc.pool)
+                .await?;
+
+        let query = QueryRequestBuilder::default()
+            .filter(Filter::length("finished", ArrayOp::LengthGt, 10))
+            .build()?;
+        let ret = svc
+            .query(query)
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+        assert!(expected > 0);
+        assert_eq!(ret.len() as i64, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn query_request_with_unknown_field_should_be_rejected() {
+        let mut query =
+            QueryRequest::new_with_dt("created_at; DROP TABLE user_stats", Utc::now(), Utc::now());
+        let Err(err) = query.to_query() else {
+            panic!("query should be rejected");
+        };
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        assert!(err.message().starts_with("Unknown timestamp field"));
+
+        query.timestamps.clear();
+        query.ids.insert("created_at".to_string(), id(&[1]));
+        let Err(err) = query.to_query() else {
+            panic!("query should be rejected");
+        };
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        assert!(err.message().starts_with("Unknown id field"));
+    }
+
+    #[test]
+    fn query_request_with_include_null_should_work() {
+        let d1 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let query = QueryRequestBuilder::default()
+            .timestamp((
+                "last_email_notification".to_string(),
+                TimeQuery {
+                    lower: None,
+                    upper: Some(Timestamp {
+                        seconds: d1.timestamp(),
+                        nanos: 0,
+                    }),
+                    include_null: true,
+                },
+            ))
+            .build()
+            .unwrap();
+        assert_eq!(
+            query.to_query().unwrap().sql(),
+            "SELECT email, name, started_but_not_finished, phone, device_id, locale, gender FROM user_stats WHERE TRUE AND (last_email_notification <= $1 OR last_email_notification IS NULL)"
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_timestamps_should_be_rejected() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let ts = Timestamp {
+            seconds: 0,
+            nanos: 1_000_000_000,
+        };
+
+        let mut range = tq(None, None);
+        range.lower = Some(ts.clone());
+        let query = QueryRequestBuilder::default()
+            .timestamp(("created_at".to_string(), range))
+            .build()
+            .unwrap();
+        let err = svc.query(query).await.err().unwrap();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let err = svc
+            .mark_notified(MarkNotifiedRequest {
+                channel: NotificationChannel::Sms as i32,
+                emails: vec!["nobody@acme.org".to_string()],
+                notified_at: Some(ts),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mark_notified_should_work() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let (email,): (String,) = sqlx::query_as("SELECT email FROM user_stats LIMIT 1")
+            .fetch_one(&svc.pool)
+            .await?;
+
+        let ret = svc
+            .mark_notified(MarkNotifiedRequest {
+                channel: NotificationChannel::Sms as i32,
+                emails: vec![email.clone(), "nobody@acme.org".to_string()],
+                notified_at: None,
+            })
+            .await?
+            .into_inner();
+        assert_eq!(ret.count, 1);
+
+        let (notified,): (Option<DateTime<Utc>>,) =
+            sqlx::query_as("SELECT last_sms_notification FROM user_stats WHERE email = $1")
+                .bind(&email)
+                .fetch_one(&svc.pool)
+                .await?;
+        assert!(notified.is_some());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn raw_query_should_work() -> Result<()> {
         let (_tdb, svc) = UserStatsService::new_for_test().await?;
@@ -163,6 +538,61 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn raw_query_with_partial_columns_should_work() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let users = svc
+            .raw_query(RawQueryRequest {
+                query: "SELECT email, name FROM user_stats LIMIT 2".to_string(),
+            })
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(users.len(), 2);
+        assert!(users
+            .iter()
+            .all(|u| u.phone.is_none() && u.device_id.is_none()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn raw_query_failing_in_postgres_should_end_with_error() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let ret = svc
+            .raw_query(RawQueryRequest {
+                query: "SELECT email, name FROM user_stats WHERE abs(-1) / 0 = 1".to_string(),
+            })
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(ret.len(), 1);
+        assert_eq!(ret[0].as_ref().unwrap_err().code(), tonic::Code::Internal);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn user_gender_and_locale_should_be_decoded() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let (email,): (String,) = sqlx::query_as(
+            "UPDATE user_stats SET gender = 'female', locale = 'zh-CN' \
+             WHERE email = (SELECT email FROM user_stats LIMIT 1) RETURNING email",
+        )
+        .fetch_one(&svc.pool)
+        .await?;
+
+        let query = format!("SELECT * FROM user_stats WHERE email = '{}'", email);
+        let mut stream = svc.raw_query(RawQueryRequest { query }).await?.into_inner();
+        let user = stream.next().await.expect("user should exist")?;
+        assert_eq!(user.gender(), Gender::Female);
+        assert_eq!(user.locale, "zh-CN");
+        Ok(())
+    }
+
     #[tokio::test]
     async fn query_should_work() -> Result<()> {
         let (_tdb, svc) = UserStatsService::new_for_test().await?;
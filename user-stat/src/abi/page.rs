@@ -0,0 +1,132 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sqlx::{Postgres, QueryBuilder};
+use tonic::{Response, Status};
+use tracing::info;
+
+use crate::{
+    pb::{QueryPageResponse, QueryRequest, User},
+    ServiceResult, UserStatsService,
+};
+
+/// page size used when the request doesn't ask for one
+const DEFAULT_PAGE_SIZE: u32 = 100;
+
+/// largest page returned, bigger pages should be streamed with `Query`
+const MAX_PAGE_SIZE: u32 = 1000;
+
+impl UserStatsService {
+    pub async fn query_page(&self, mut query: QueryRequest) -> ServiceResult<QueryPageResponse> {
+        let page_size = match query.page_size {
+            0 => DEFAULT_PAGE_SIZE,
+            n => n.min(MAX_PAGE_SIZE),
+        };
+        // fetch one more user to know if there is a next page
+        query.page_size = page_size + 1;
+        let mut builder = query.to_query()?;
+        info!("Generated SQL: {}", builder.sql());
+
+        let mut users = builder
+            .build_query_as::<User>()
+            .fetch_all(&self.inner.pool)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to fetch data: {}", e)))?;
+
+        let mut next_page_token = String::new();
+        if users.len() > page_size as usize {
+            users.truncate(page_size as usize);
+            if let Some(last) = users.last() {
+                next_page_token = encode_page_token(&last.email);
+            }
+        }
+
+        Ok(Response::new(QueryPageResponse {
+            users,
+            next_page_token,
+        }))
+    }
+}
+
+/// resume after the last user of the previous page, users are ordered by their email which is
+/// unique, so pages stay stable while users are added or removed
+#[allow(clippy::result_large_err)]
+pub(super) fn push_page(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    page_size: u32,
+    page_token: &str,
+) -> Result<(), Status> {
+    if !page_token.is_empty() {
+        let email = decode_page_token(page_token)?;
+        builder.push(" AND email > ").push_bind(email);
+    }
+    if page_size > 0 || !page_token.is_empty() {
+        builder.push(" ORDER BY email");
+    }
+    if page_size > 0 {
+        builder.push(" LIMIT ").push_bind(page_size as i64);
+    }
+    Ok(())
+}
+
+/// the token is the last email of the page, encoded so that clients treat it as opaque
+fn encode_page_token(email: &str) -> String {
+    URL_SAFE_NO_PAD.encode(email)
+}
+
+#[allow(clippy::result_large_err)]
+fn decode_page_token(token: &str) -> Result<String, Status> {
+    URL_SAFE_NO_PAD
+        .decode(token)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| Status::invalid_argument(format!("Invalid page token: {:?}", token)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::pb::QueryRequestBuilder;
+
+    #[test]
+    fn page_token_should_round_trip() {
+        let token = encode_page_token("alice@acme.org");
+        assert_eq!(decode_page_token(&token).unwrap(), "alice@acme.org");
+        let err = decode_page_token("not a token!").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn query_pages_should_cover_every_user_once() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let (total,): (i64,) = sqlx::query_as("SELECT count(*) FROM user_stats")
+            .fetch_one(&svc.pool)
+            .await?;
+
+        let mut emails = HashSet::new();
+        let mut page_token = String::new();
+        let mut pages = 0;
+        loop {
+            let query = QueryRequestBuilder::default()
+                .page_size(50u32)
+                .page_token(page_token)
+                .build()?;
+            let page = svc.query_page(query).await?.into_inner();
+            assert!(page.users.len() <= 50);
+            pages += 1;
+            for user in page.users {
+                assert!(emails.insert(user.email), "user returned twice");
+            }
+            if page.next_page_token.is_empty() {
+                break;
+            }
+            page_token = page.next_page_token;
+        }
+
+        assert_eq!(emails.len() as i64, total);
+        assert_eq!(pages, (total + 49) / 50);
+        Ok(())
+    }
+}
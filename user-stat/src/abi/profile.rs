@@ -0,0 +1,150 @@
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use prost_types::Timestamp;
+use sqlx::{Postgres, QueryBuilder};
+use tonic::{Response, Status};
+use tracing::info;
+
+use super::column;
+use crate::{pb::QueryRequest, ProfileStream, ServiceResult, UserStatsService};
+
+/// columns of `user_stats` with a field in `UserProfile`, in the same order as the message fields
+pub(super) const PROFILE_COLUMNS: &[&str] = &[
+    "email",
+    "name",
+    "gender",
+    "locale",
+    "phone",
+    "device_id",
+    "created_at",
+    "last_visited_at",
+    "last_watched_at",
+    "last_email_notification",
+    "last_in_app_notification",
+    "last_sms_notification",
+    "recent_watched",
+    "viewed_but_not_started",
+    "started_but_not_finished",
+    "finished",
+];
+
+/// a nullable timestamp column, decoded into an optional `Timestamp` field
+#[derive(Debug, sqlx::Type)]
+#[sqlx(transparent)]
+pub(crate) struct DbTimestamp(Option<DateTime<Utc>>);
+
+/// a nullable content id array column, a null array is decoded as empty
+#[derive(Debug, sqlx::Type)]
+#[sqlx(transparent, no_pg_array)]
+pub(crate) struct DbIds(Option<Vec<i32>>);
+
+impl UserStatsService {
+    pub async fn query_profiles(&self, query: QueryRequest) -> ServiceResult<ProfileStream> {
+        let builder = query.to_profile_query()?;
+        info!("Generated SQL: {}", builder.sql());
+
+        Ok(Response::new(self.fetch_rows(builder)))
+    }
+}
+
+impl QueryRequest {
+    /// the sql of the query selecting the projected `UserProfile` fields
+    #[allow(clippy::result_large_err)]
+    pub fn to_profile_query(&self) -> Result<QueryBuilder<'static, Postgres>, Status> {
+        let columns = self.projection()?.join(", ");
+        self.select(&columns)
+    }
+
+    /// the columns of the fields, always starting with email. Every column if no field is given
+    #[allow(clippy::result_large_err)]
+    pub(super) fn projection(&self) -> Result<Vec<&'static str>, Status> {
+        if self.fields.is_empty() {
+            return Ok(PROFILE_COLUMNS.to_vec());
+        }
+
+        let mut columns = vec!["email"];
+        for field in &self.fields {
+            columns.push(column(field, PROFILE_COLUMNS, "profile")?);
+        }
+        Ok(columns.into_iter().unique().collect())
+    }
+}
+
+impl From<DbTimestamp> for Option<Timestamp> {
+    fn from(ts: DbTimestamp) -> Self {
+        ts.0.map(|dt| Timestamp {
+            seconds: dt.timestamp(),
+            nanos: dt.timestamp_subsec_nanos() as i32,
+        })
+    }
+}
+
+impl From<DbIds> for Vec<i32> {
+    fn from(ids: DbIds) -> Self {
+        ids.0.unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::pb::{Gender, QueryRequestBuilder};
+
+    #[test]
+    fn profile_query_should_project_fields() {
+        let query = QueryRequestBuilder::default()
+            .field("finished")
+            .field("email")
+            .field("created_at")
+            .build()
+            .unwrap();
+        assert_eq!(
+            query.to_profile_query().unwrap().sql(),
+            "SELECT email, finished, created_at FROM user_stats WHERE TRUE"
+        );
+
+        let query = QueryRequestBuilder::default()
+            .field("password")
+            .build()
+            .unwrap();
+        let Err(err) = query.to_profile_query() else {
+            panic!("query should be rejected");
+        };
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn query_profiles_should_fill_projected_fields() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let query = QueryRequestBuilder::default()
+            .field("started_but_not_finished")
+            .field("created_at")
+            .page_size(10u32)
+            .build()?;
+        let profiles = svc
+            .query_profiles(query)
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(profiles.len(), 10);
+        for profile in profiles {
+            let profile = profile?;
+            assert!(!profile.email.is_empty());
+            assert!(profile.name.is_empty());
+            assert!(profile.created_at.is_some());
+            assert!(profile.finished.is_empty());
+        }
+
+        let query = QueryRequestBuilder::default().page_size(1u32).build()?;
+        let mut profiles = svc.query_profiles(query).await?.into_inner();
+        let profile = profiles.next().await.expect("a profile")?;
+        assert!(!profile.name.is_empty());
+        assert_eq!(profile.gender(), Gender::Unknown);
+        assert_eq!(profile.locale.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+use std::{fmt, ops::ControlFlow};
+
+use sqlparser::{
+    ast::{
+        visit_expressions, visit_relations, Expr, Query, SelectItem, SetExpr, Statement,
+        TableFactor,
+    },
+    dialect::PostgreSqlDialect,
+    parser::Parser,
+};
+use tonic::Status;
+
+/// the only table raw queries may read
+const TABLE: &str = "user_stats";
+
+/// columns every raw query must project, `User` can't be built without them
+const REQUIRED_COLUMNS: [&str; 2] = ["email", "name"];
+
+/// functions raw queries may call, none of them has side effects or reads other tables
+const ALLOWED_FUNCTIONS: &[&str] = &[
+    "abs",
+    "array_length",
+    "cardinality",
+    "coalesce",
+    "current_date",
+    "current_timestamp",
+    "date_trunc",
+    "greatest",
+    "least",
+    "length",
+    "lower",
+    "now",
+    "upper",
+];
+
+/// why a raw query is rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    Parse(String),
+    Empty,
+    MultipleStatements(usize),
+    NotSelect(String),
+    Unsupported(&'static str),
+    Table(String),
+    Projection(String),
+    MissingColumn(&'static str),
+    Function(String),
+}
+
+/// make sure the query is a single SELECT over `user_stats` which projects `email` and `name`
+/// and only calls allowed functions
+pub fn validate(sql: &str) -> Result<(), QueryError> {
+    let statements = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+        .map_err(|e| QueryError::Parse(e.to_string()))?;
+    let statement = match statements.as_slice() {
+        [] => return Err(QueryError::Empty),
+        [statement] => statement,
+        _ => return Err(QueryError::MultipleStatements(statements.len())),
+    };
+    let Statement::Query(query) = statement else {
+        return Err(QueryError::NotSelect(statement_kind(statement)));
+    };
+
+    validate_query(query)?;
+
+    let ret = visit_expressions(statement, |expr| match expr {
+        Expr::Subquery(_) | Expr::Exists { .. } | Expr::InSubquery { .. } => {
+            ControlFlow::Break(QueryError::Unsupported("subqueries"))
+        }
+        Expr::Function(f) => {
+            let name = f.name.to_string().to_lowercase();
+            if ALLOWED_FUNCTIONS.contains(&name.as_str()) {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(QueryError::Function(name))
+            }
+        }
+        _ => ControlFlow::Continue(()),
+    });
+    if let ControlFlow::Break(e) = ret {
+        return Err(e);
+    }
+
+    let ret = visit_relations(statement, |name| {
+        let name = name.to_string();
+        if name.eq_ignore_ascii_case(TABLE) {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(QueryError::Table(name))
+        }
+    });
+    if let ControlFlow::Break(e) = ret {
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+fn validate_query(query: &Query) -> Result<(), QueryError> {
+    if query.with.is_some() {
+        return Err(QueryError::Unsupported("WITH clauses"));
+    }
+    if !query.locks.is_empty() {
+        return Err(QueryError::Unsupported("locking clauses"));
+    }
+    if query.for_clause.is_some() {
+        return Err(QueryError::Unsupported("FOR clauses"));
+    }
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return Err(QueryError::Unsupported("set operations and VALUES"));
+    };
+    if select.into.is_some() {
+        return Err(QueryError::Unsupported("SELECT INTO"));
+    }
+
+    let [from] = select.from.as_slice() else {
+        return Err(QueryError::Unsupported(
+            "selecting from other than a single table",
+        ));
+    };
+    if !from.joins.is_empty() {
+        return Err(QueryError::Unsupported("joins"));
+    }
+    let TableFactor::Table {
+        name, args: None, ..
+    } = &from.relation
+    else {
+        return Err(QueryError::Unsupported(
+            "selecting from other than a single table",
+        ));
+    };
+    if !name.to_string().eq_ignore_ascii_case(TABLE) {
+        return Err(QueryError::Table(name.to_string()));
+    }
+
+    let mut columns = Vec::new();
+    for item in &select.projection {
+        match item {
+            SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..) => {
+                columns.extend(REQUIRED_COLUMNS.iter().map(|c| c.to_string()))
+            }
+            SelectItem::UnnamedExpr(Expr::Identifier(ident)) => columns.push(ident.value.clone()),
+            SelectItem::UnnamedExpr(Expr::CompoundIdentifier(idents)) => {
+                columns.extend(idents.last().map(|ident| ident.value.clone()))
+            }
+            _ => return Err(QueryError::Projection(item.to_string())),
+        }
+    }
+    for column in REQUIRED_COLUMNS {
+        if !columns.iter().any(|c| c.eq_ignore_ascii_case(column)) {
+            return Err(QueryError::MissingColumn(column));
+        }
+    }
+
+    Ok(())
+}
+
+/// leading keyword of the statement, e.g. DROP or INSERT
+fn statement_kind(statement: &Statement) -> String {
+    statement
+        .to_string()
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_uppercase()
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::Parse(e) => write!(f, "failed to parse query: {}", e),
+            QueryError::Empty => write!(f, "query is empty"),
+            QueryError::MultipleStatements(n) => {
+                write!(f, "only a single statement is allowed, got {}", n)
+            }
+            QueryError::NotSelect(kind) => {
+                write!(f, "only SELECT statements are allowed, got {}", kind)
+            }
+            QueryError::Unsupported(what) => write!(f, "{} are not allowed", what),
+            QueryError::Table(name) => {
+                write!(f, "only {} can be queried, got {}", TABLE, name)
+            }
+            QueryError::Projection(item) => {
+                write!(f, "only columns can be projected, got {}", item)
+            }
+            QueryError::MissingColumn(column) => {
+                write!(f, "column {} must be projected", column)
+            }
+            QueryError::Function(name) => write!(f, "function {} is not allowed", name),
+        }
+    }
+}
+
+impl From<QueryError> for Status {
+    fn from(e: QueryError) -> Self {
+        Status::invalid_argument(format!("Invalid query: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pb::RawQueryRequest, UserStatsService};
+    use anyhow::Result;
+    use futures::StreamExt;
+
+    const VALID_QUERIES: [&str; 5] = [
+        "SELECT * FROM user_stats WHERE created_at > '2024-01-01' LIMIT 5",
+        "select email, name, phone from user_stats u where u.last_visited_at > now() - interval '7 days'",
+        "SELECT email, name FROM user_stats WHERE array[1, 2] <@ finished ORDER BY email",
+        "SELECT email, name FROM user_stats WHERE coalesce(cardinality(finished), 0) > 3",
+        "SELECT u.email, u.name, u.device_id FROM user_stats u LIMIT 3",
+    ];
+
+    #[test]
+    fn valid_queries_should_pass() {
+        for query in VALID_QUERIES {
+            assert_eq!(validate(query), Ok(()), "{}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn valid_queries_should_be_decoded_by_raw_query() -> Result<()> {
+        let (_tdb, svc) = UserStatsService::new_for_test().await?;
+        let mut total = 0;
+        for query in VALID_QUERIES {
+            let ret = svc
+                .raw_query(RawQueryRequest {
+                    query: query.to_string(),
+                })
+                .await?
+                .into_inner()
+                .collect::<Vec<_>>()
+                .await;
+            total += ret.len();
+            for user in ret {
+                assert!(user.is_ok(), "{}: {:?}", query, user);
+            }
+        }
+        assert!(total > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_queries_should_be_rejected_with_reason() {
+        let cases = [
+            ("SELEC * FROM user_stats", "failed to parse query"),
+            ("", "query is empty"),
+            (
+                "SELECT * FROM user_stats; DROP TABLE user_stats",
+                "only a single statement is allowed, got 2",
+            ),
+            (
+                "DROP TABLE user_stats",
+                "only SELECT statements are allowed, got DROP",
+            ),
+            (
+                "UPDATE user_stats SET name = 'x'",
+                "only SELECT statements are allowed, got UPDATE",
+            ),
+            (
+                "SELECT email, name FROM pg_shadow",
+                "only user_stats can be queried",
+            ),
+            (
+                "SELECT email, name FROM user_stats WHERE email IN (SELECT usename FROM pg_user)",
+                "subqueries are not allowed",
+            ),
+            (
+                "SELECT * FROM user_stats a JOIN user_stats b ON a.email = b.email",
+                "joins are not allowed",
+            ),
+            (
+                "WITH u AS (SELECT * FROM user_stats) SELECT * FROM u",
+                "WITH clauses are not allowed",
+            ),
+            (
+                "SELECT * FROM user_stats FOR UPDATE",
+                "locking clauses are not allowed",
+            ),
+            (
+                "SELECT email FROM user_stats",
+                "column name must be projected",
+            ),
+            (
+                "SELECT email, upper(name) AS name FROM user_stats",
+                "only columns can be projected",
+            ),
+            (
+                "SELECT * FROM user_stats WHERE pg_sleep(10) IS NULL",
+                "function pg_sleep is not allowed",
+            ),
+        ];
+        for (query, reason) in cases {
+            let err = validate(query).unwrap_err();
+            assert!(err.to_string().starts_with(reason), "{}: {}", query, err);
+            assert_eq!(Status::from(err).code(), tonic::Code::InvalidArgument);
+        }
+    }
+}
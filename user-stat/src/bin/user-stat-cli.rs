@@ -0,0 +1,206 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use prost_types::Timestamp;
+use tokio::{
+    fs,
+    io::{self, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use user_stat::pb::{
+    user_stats_client::UserStatsClient, DataFormat, ExportRequest, IdQuery, ImportRequest,
+    QueryRequest, TimeQuery,
+};
+
+/// size of the chunks a file is imported in
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// import users into and export users from the user stats service
+#[derive(Debug, Parser)]
+struct Args {
+    /// address of the user stats service
+    #[arg(long, default_value = "http://[::1]:50001")]
+    server: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// insert new users and update existing ones from a csv or ndjson file
+    Import {
+        file: PathBuf,
+        /// guessed from the file extension if not given
+        #[arg(long)]
+        format: Option<Format>,
+    },
+    /// write the users of a segment to a file, or to stdout
+    Export {
+        #[arg(long)]
+        format: Format,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// profile fields to export, every field if not given
+        #[arg(long, value_delimiter = ',')]
+        fields: Vec<String>,
+        /// users with a timestamp in a range, e.g. created_at=2024-01-01T00:00:00Z..
+        #[arg(long, value_parser = parse_time)]
+        time: Vec<(String, TimeQuery)>,
+        /// users with every content id in an id column, e.g. finished=1,2,3
+        #[arg(long, value_parser = parse_ids)]
+        ids: Vec<(String, IdQuery)>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Ndjson,
+    Parquet,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut client = UserStatsClient::connect(args.server).await?;
+
+    match args.command {
+        Command::Import { file, format } => {
+            let format = match format {
+                Some(format) => format,
+                None => Format::from_path(&file)?,
+            };
+            if let Format::Parquet = format {
+                bail!("Parquet files can't be imported, use csv or ndjson");
+            }
+            let mut reader = fs::File::open(&file)
+                .await
+                .with_context(|| format!("Failed to open {}", file.display()))?;
+
+            // the server parses the chunks as they arrive, so the file is never read as a whole
+            let (tx, rx) = mpsc::channel(4);
+            let call = tokio::spawn(async move { client.import(ReceiverStream::new(rx)).await });
+            let mut buf = vec![0; CHUNK_SIZE];
+            loop {
+                let n = match reader.read(&mut buf).await {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) => {
+                        // ending the stream would import the part read so far, cancel the
+                        // call while it is still open instead so the server rolls it back
+                        call.abort();
+                        let _ = call.await;
+                        return Err(e).context(format!("Failed to read {}", file.display()));
+                    }
+                };
+                let req = ImportRequest {
+                    format: DataFormat::from(format) as i32,
+                    data: buf[..n].to_vec(),
+                };
+                // the server has stopped reading, its response tells why
+                if tx.send(req).await.is_err() {
+                    break;
+                }
+            }
+            drop(tx);
+
+            let ret = call.await??.into_inner();
+            eprintln!("{} users inserted, {} updated", ret.inserted, ret.updated);
+        }
+        Command::Export {
+            format,
+            output,
+            fields,
+            time,
+            ids,
+        } => {
+            let query = QueryRequest {
+                timestamps: time.into_iter().collect(),
+                ids: ids.into_iter().collect(),
+                fields,
+                ..Default::default()
+            };
+            let req = ExportRequest {
+                query: Some(query),
+                format: DataFormat::from(format) as i32,
+            };
+
+            let mut writer: Box<dyn AsyncWrite + Unpin> = match output {
+                Some(path) => Box::new(fs::File::create(path).await?),
+                None => Box::new(io::stdout()),
+            };
+            let mut stream = client.export(req).await?.into_inner();
+            while let Some(chunk) = stream.message().await? {
+                writer.write_all(&chunk.data).await?;
+            }
+            writer.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => Ok(Format::Csv),
+            Some("ndjson") | Some("jsonl") => Ok(Format::Ndjson),
+            Some("parquet") => Ok(Format::Parquet),
+            _ => bail!("Unknown format of {}, use --format", path.display()),
+        }
+    }
+}
+
+impl From<Format> for DataFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Csv => DataFormat::Csv,
+            Format::Ndjson => DataFormat::Ndjson,
+            Format::Parquet => DataFormat::Parquet,
+        }
+    }
+}
+
+/// `field=lower..upper`, either bound may be left out
+fn parse_time(s: &str) -> Result<(String, TimeQuery)> {
+    let (field, range) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected field=lower..upper"))?;
+    let (lower, upper) = range
+        .split_once("..")
+        .ok_or_else(|| anyhow!("expected field=lower..upper"))?;
+    let ts = |s: &str| -> Result<Option<Timestamp>> {
+        if s.is_empty() {
+            return Ok(None);
+        }
+        let dt: DateTime<Utc> = s.parse()?;
+        Ok(Some(Timestamp {
+            seconds: dt.timestamp(),
+            nanos: dt.timestamp_subsec_nanos() as i32,
+        }))
+    };
+    let tq = TimeQuery {
+        lower: ts(lower)?,
+        upper: ts(upper)?,
+        include_null: false,
+    };
+    Ok((field.to_string(), tq))
+}
+
+/// `field=id,id,..`
+fn parse_ids(s: &str) -> Result<(String, IdQuery)> {
+    let (field, ids) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected field=id,id"))?;
+    let ids = ids
+        .split(',')
+        .map(|id| id.trim().parse())
+        .collect::<Result<_, _>>()?;
+    let iq = IdQuery {
+        ids,
+        ..Default::default()
+    };
+    Ok((field.to_string(), iq))
+}
@@ -6,6 +6,17 @@ use std::{env, fs::File};
 pub struct AppConfig {
     pub server: ServerConfig,
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub ingest: IngestConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IngestConfig {
+    /// events applied in a single transaction
+    pub batch_size: usize,
+    /// content ids kept in recent_watched, the oldest ones are dropped first
+    pub recent_watched: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,6 +30,15 @@ pub struct ServerConfig {
     pub db_url: String,
 }
 
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            recent_watched: 10,
+        }
+    }
+}
+
 impl AppConfig {
     pub fn load() -> Result<Self> {
         // read from  ./app.yml, or /etc/config/app.yml, or from env CHAT_CONFIG
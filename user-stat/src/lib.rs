@@ -6,16 +6,20 @@ pub mod pb;
 use futures::Stream;
 use pb::{
     user_stats_server::{UserStats, UserStatsServer},
-    QueryRequest, RawQueryRequest, User,
+    AggregateRequest, AggregateResponse, CountResponse, ExportChunk, ExportRequest, ImportRequest,
+    ImportResponse, IngestResponse, MarkNotifiedRequest, MarkNotifiedResponse, QueryPageResponse,
+    QueryRequest, RawQueryRequest, User, UserEvent, UserProfile,
 };
 use sqlx::PgPool;
 use std::{ops::Deref, pin::Pin, sync::Arc};
-use tonic::{async_trait, Request, Response, Status};
+use tonic::{async_trait, Request, Response, Status, Streaming};
 
 pub use config::AppConfig;
 
 type ServiceResult<T> = Result<Response<T>, Status>;
 type ResponseStream = Pin<Box<dyn Stream<Item = Result<User, Status>> + Send>>;
+type ExportStream = Pin<Box<dyn Stream<Item = Result<ExportChunk, Status>> + Send>>;
+type ProfileStream = Pin<Box<dyn Stream<Item = Result<UserProfile, Status>> + Send>>;
 
 #[derive(Clone)]
 pub struct UserStatsService {
@@ -32,12 +36,40 @@ pub struct UserStatsServiceInner {
 impl UserStats for UserStatsService {
     type QueryStream = ResponseStream;
     type RawQueryStream = ResponseStream;
+    type QueryProfilesStream = ProfileStream;
+    type ExportStream = ExportStream;
 
     async fn query(&self, request: Request<QueryRequest>) -> ServiceResult<Self::QueryStream> {
         let query = request.into_inner();
         self.query(query).await
     }
 
+    async fn query_profiles(
+        &self,
+        request: Request<QueryRequest>,
+    ) -> ServiceResult<Self::QueryProfilesStream> {
+        let query = request.into_inner();
+        self.query_profiles(query).await
+    }
+
+    async fn query_page(&self, request: Request<QueryRequest>) -> ServiceResult<QueryPageResponse> {
+        let query = request.into_inner();
+        self.query_page(query).await
+    }
+
+    async fn count(&self, request: Request<QueryRequest>) -> ServiceResult<CountResponse> {
+        let query = request.into_inner();
+        self.count(query).await
+    }
+
+    async fn aggregate(
+        &self,
+        request: Request<AggregateRequest>,
+    ) -> ServiceResult<AggregateResponse> {
+        let req = request.into_inner();
+        self.aggregate(req).await
+    }
+
     async fn raw_query(
         &self,
         request: Request<RawQueryRequest>,
@@ -45,6 +77,35 @@ impl UserStats for UserStatsService {
         let query = request.into_inner();
         self.raw_query(query).await
     }
+
+    async fn ingest(
+        &self,
+        request: Request<Streaming<UserEvent>>,
+    ) -> ServiceResult<IngestResponse> {
+        let stream = request.into_inner();
+        self.ingest(stream).await
+    }
+
+    async fn import(
+        &self,
+        request: Request<Streaming<ImportRequest>>,
+    ) -> ServiceResult<ImportResponse> {
+        let stream = request.into_inner();
+        self.import(stream).await
+    }
+
+    async fn export(&self, request: Request<ExportRequest>) -> ServiceResult<Self::ExportStream> {
+        let req = request.into_inner();
+        self.export(req).await
+    }
+
+    async fn mark_notified(
+        &self,
+        request: Request<MarkNotifiedRequest>,
+    ) -> ServiceResult<MarkNotifiedResponse> {
+        let req = request.into_inner();
+        self.mark_notified(req).await
+    }
 }
 
 impl UserStatsService {
@@ -122,13 +183,17 @@ pub mod test_utils {
     }
 
     pub fn id(id: &[u32]) -> IdQuery {
-        IdQuery { ids: id.to_vec() }
+        IdQuery {
+            ids: id.to_vec(),
+            ..Default::default()
+        }
     }
 
     pub fn tq(lower: Option<i64>, upper: Option<i64>) -> TimeQuery {
         TimeQuery {
             lower: lower.map(to_ts),
             upper: upper.map(to_ts),
+            include_null: false,
         }
     }
 
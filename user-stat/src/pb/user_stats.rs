@@ -12,6 +12,81 @@ pub struct User {
     #[prost(string, tag = "2")]
     #[builder(setter(into))]
     pub name: ::prost::alloc::string::String,
+    /// content ids the user has started but not finished yet
+    #[prost(int32, repeated, tag = "3")]
+    #[builder(setter(into), default)]
+    #[sqlx(default)]
+    pub started_but_not_finished: ::prost::alloc::vec::Vec<i32>,
+    /// phone number for sms notifications
+    #[prost(string, optional, tag = "4")]
+    #[sqlx(default)]
+    pub phone: ::core::option::Option<::prost::alloc::string::String>,
+    /// device id for in-app notifications
+    #[prost(string, optional, tag = "5")]
+    #[sqlx(default)]
+    pub device_id: ::core::option::Option<::prost::alloc::string::String>,
+    /// preferred locale of the user, e.g. zh-CN
+    #[prost(string, tag = "6")]
+    #[builder(setter(into), default)]
+    #[sqlx(default)]
+    pub locale: ::prost::alloc::string::String,
+    #[prost(enumeration = "Gender", tag = "7")]
+    #[builder(setter(into), default)]
+    #[sqlx(try_from = "crate::abi::DbGender", default)]
+    pub gender: i32,
+}
+/// every column of user_stats, fields left out of the projection are unset
+#[derive(sqlx::FromRow)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UserProfile {
+    #[prost(string, tag = "1")]
+    pub email: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    #[sqlx(default)]
+    pub name: ::prost::alloc::string::String,
+    #[prost(enumeration = "Gender", tag = "3")]
+    #[sqlx(try_from = "crate::abi::DbGender", default)]
+    pub gender: i32,
+    #[prost(string, tag = "4")]
+    #[sqlx(default)]
+    pub locale: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "5")]
+    #[sqlx(default)]
+    pub phone: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "6")]
+    #[sqlx(default)]
+    pub device_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "7")]
+    #[sqlx(try_from = "crate::abi::DbTimestamp", default)]
+    pub created_at: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "8")]
+    #[sqlx(try_from = "crate::abi::DbTimestamp", default)]
+    pub last_visited_at: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "9")]
+    #[sqlx(try_from = "crate::abi::DbTimestamp", default)]
+    pub last_watched_at: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "10")]
+    #[sqlx(try_from = "crate::abi::DbTimestamp", default)]
+    pub last_email_notification: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "11")]
+    #[sqlx(try_from = "crate::abi::DbTimestamp", default)]
+    pub last_in_app_notification: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "12")]
+    #[sqlx(try_from = "crate::abi::DbTimestamp", default)]
+    pub last_sms_notification: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(int32, repeated, tag = "13")]
+    #[sqlx(try_from = "crate::abi::DbIds", default)]
+    pub recent_watched: ::prost::alloc::vec::Vec<i32>,
+    #[prost(int32, repeated, tag = "14")]
+    #[sqlx(try_from = "crate::abi::DbIds", default)]
+    pub viewed_but_not_started: ::prost::alloc::vec::Vec<i32>,
+    #[prost(int32, repeated, tag = "15")]
+    #[sqlx(try_from = "crate::abi::DbIds", default)]
+    pub started_but_not_finished: ::prost::alloc::vec::Vec<i32>,
+    #[prost(int32, repeated, tag = "16")]
+    #[sqlx(try_from = "crate::abi::DbIds", default)]
+    pub finished: ::prost::alloc::vec::Vec<i32>,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -25,12 +100,110 @@ pub struct QueryRequest {
     #[prost(map = "string, message", tag = "2")]
     #[builder(setter(each(name = "id", into)))]
     pub ids: ::std::collections::HashMap<::prost::alloc::string::String, IdQuery>,
+    /// ANDed with the conditions above
+    #[prost(message, optional, tag = "3")]
+    #[builder(setter(into, strip_option), default)]
+    pub filter: ::core::option::Option<Filter>,
+    /// max number of users returned, 0 returns every match. Paged results are
+    /// ordered by email
+    #[prost(uint32, tag = "4")]
+    #[builder(setter(into), default)]
+    pub page_size: u32,
+    /// next_page_token of the previous page, empty for the first page
+    #[prost(string, tag = "5")]
+    #[builder(setter(into), default)]
+    pub page_token: ::prost::alloc::string::String,
+    /// UserProfile fields returned by QueryProfiles, every field if empty. email
+    /// is always returned
+    #[prost(string, repeated, tag = "6")]
+    #[builder(setter(each(name = "field", into)), default)]
+    pub fields: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryPageResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub users: ::prost::alloc::vec::Vec<User>,
+    /// token of the page following this one, empty on the last page
+    #[prost(string, tag = "2")]
+    pub next_page_token: ::prost::alloc::string::String,
+}
+/// a boolean expression over the columns of user_stats
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Filter {
+    #[prost(oneof = "filter::Filter", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub filter: ::core::option::Option<filter::Filter>,
+}
+/// Nested message and enum types in `Filter`.
+pub mod filter {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Filter {
+        /// true if every filter matches, true if empty
+        #[prost(message, tag = "1")]
+        And(super::FilterList),
+        /// true if any filter matches, false if empty
+        #[prost(message, tag = "2")]
+        Or(super::FilterList),
+        #[prost(message, tag = "3")]
+        Not(::prost::alloc::boxed::Box<super::Filter>),
+        #[prost(message, tag = "4")]
+        Time(super::TimeRange),
+        #[prost(message, tag = "5")]
+        Array(super::ArrayFilter),
+        #[prost(enumeration = "super::Gender", tag = "6")]
+        Gender(i32),
+        #[prost(message, tag = "7")]
+        Null(super::NullCheck),
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FilterList {
+    #[prost(message, repeated, tag = "1")]
+    pub filters: ::prost::alloc::vec::Vec<Filter>,
+}
+/// a timestamp column within the range, either bound may be left open
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TimeRange {
+    #[prost(string, tag = "1")]
+    pub field: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub lower: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "3")]
+    pub upper: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ArrayFilter {
+    #[prost(string, tag = "1")]
+    pub field: ::prost::alloc::string::String,
+    #[prost(enumeration = "ArrayOp", tag = "2")]
+    pub op: i32,
+    #[prost(uint32, repeated, tag = "3")]
+    pub ids: ::prost::alloc::vec::Vec<u32>,
+    /// length compared by the length operators
+    #[prost(uint32, tag = "4")]
+    pub length: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NullCheck {
+    #[prost(string, tag = "1")]
+    pub field: ::prost::alloc::string::String,
+    /// match a set value instead
+    #[prost(bool, tag = "2")]
+    pub not_null: bool,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RawQueryRequest {
+    /// a single SELECT over user_stats which projects at least email and name,
+    /// only a few side effect free functions may be called
     #[prost(string, tag = "1")]
     #[builder(setter(into))]
     pub query: ::prost::alloc::string::String,
@@ -44,6 +217,10 @@ pub struct TimeQuery {
     pub lower: ::core::option::Option<::prost_types::Timestamp>,
     #[prost(message, optional, tag = "2")]
     pub upper: ::core::option::Option<::prost_types::Timestamp>,
+    /// also match users whose timestamp is not set
+    #[prost(bool, tag = "3")]
+    #[builder(default)]
+    pub include_null: bool,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -52,6 +229,360 @@ pub struct TimeQuery {
 pub struct IdQuery {
     #[prost(uint32, repeated, tag = "1")]
     pub ids: ::prost::alloc::vec::Vec<u32>,
+    #[prost(enumeration = "ArrayOp", tag = "2")]
+    pub op: i32,
+    /// length compared by the length operators
+    #[prost(uint32, tag = "3")]
+    pub length: u32,
+}
+/// request to record that users have been notified
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MarkNotifiedRequest {
+    #[prost(enumeration = "NotificationChannel", tag = "1")]
+    pub channel: i32,
+    /// emails of the notified users
+    #[prost(string, repeated, tag = "2")]
+    pub emails: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// when the users have been notified, now if not set
+    #[prost(message, optional, tag = "3")]
+    pub notified_at: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MarkNotifiedResponse {
+    /// number of users updated
+    #[prost(uint64, tag = "1")]
+    pub count: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CountResponse {
+    #[prost(uint64, tag = "1")]
+    pub count: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AggregateRequest {
+    /// users to aggregate, pagination is ignored
+    #[prost(message, optional, tag = "1")]
+    pub query: ::core::option::Option<QueryRequest>,
+    /// histograms to compute, every histogram if empty
+    #[prost(enumeration = "Histogram", repeated, tag = "2")]
+    pub histograms: ::prost::alloc::vec::Vec<i32>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Bucket {
+    /// empty for users without a value
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub count: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistogramResult {
+    #[prost(enumeration = "Histogram", tag = "1")]
+    pub histogram: i32,
+    /// ordered by key
+    #[prost(message, repeated, tag = "2")]
+    pub buckets: ::prost::alloc::vec::Vec<Bucket>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AggregateResponse {
+    /// number of users matching the query
+    #[prost(uint64, tag = "1")]
+    pub total: u64,
+    #[prost(message, repeated, tag = "2")]
+    pub histograms: ::prost::alloc::vec::Vec<HistogramResult>,
+}
+/// something a user did, applied to the user's row of user_stats
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UserEvent {
+    #[prost(string, tag = "1")]
+    pub email: ::prost::alloc::string::String,
+    /// when the event happened, now if unset
+    #[prost(message, optional, tag = "2")]
+    pub occurred_at: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(oneof = "user_event::Event", tags = "3, 4, 5, 6, 7, 8")]
+    pub event: ::core::option::Option<user_event::Event>,
+}
+/// Nested message and enum types in `UserEvent`.
+pub mod user_event {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Event {
+        #[prost(message, tag = "3")]
+        Registered(super::Registered),
+        #[prost(message, tag = "4")]
+        Visited(super::Visited),
+        /// the user opened the page of the content
+        #[prost(message, tag = "5")]
+        Viewed(super::ContentEvent),
+        #[prost(message, tag = "6")]
+        Started(super::ContentEvent),
+        #[prost(message, tag = "7")]
+        Finished(super::ContentEvent),
+        #[prost(message, tag = "8")]
+        Notified(super::Notified),
+    }
+}
+/// creates the user, or updates the profile of an existing one
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Registered {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(enumeration = "Gender", tag = "2")]
+    pub gender: i32,
+    /// en if empty
+    #[prost(string, tag = "3")]
+    pub locale: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "4")]
+    pub phone: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "5")]
+    pub device_id: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Visited {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ContentEvent {
+    #[prost(uint32, tag = "1")]
+    pub content_id: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Notified {
+    #[prost(enumeration = "NotificationChannel", tag = "1")]
+    pub channel: i32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IngestResponse {
+    /// events applied to a user
+    #[prost(uint64, tag = "1")]
+    pub applied: u64,
+    /// events of users which don't exist
+    #[prost(uint64, tag = "2")]
+    pub skipped: u64,
+}
+/// a chunk of the file to import, the format of the first chunk is used. Rows
+/// have the columns of UserProfile, only email and name are required
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportRequest {
+    #[prost(enumeration = "DataFormat", tag = "1")]
+    pub format: i32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportResponse {
+    #[prost(uint64, tag = "1")]
+    pub inserted: u64,
+    #[prost(uint64, tag = "2")]
+    pub updated: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportRequest {
+    /// users to export, with the projected fields as columns
+    #[prost(message, optional, tag = "1")]
+    pub query: ::core::option::Option<QueryRequest>,
+    #[prost(enumeration = "DataFormat", tag = "2")]
+    pub format: i32,
+}
+/// a chunk of the exported file, in order
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportChunk {
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Gender {
+    Unknown = 0,
+    Female = 1,
+    Male = 2,
+}
+impl Gender {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Gender::Unknown => "GENDER_UNKNOWN",
+            Gender::Female => "GENDER_FEMALE",
+            Gender::Male => "GENDER_MALE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "GENDER_UNKNOWN" => Some(Self::Unknown),
+            "GENDER_FEMALE" => Some(Self::Female),
+            "GENDER_MALE" => Some(Self::Male),
+            _ => None,
+        }
+    }
+}
+/// how the ids of an id query or array filter are matched against a content id
+/// column
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ArrayOp {
+    /// the column contains every id
+    ContainsAll = 0,
+    /// the column contains at least one of the ids
+    ContainsAny = 1,
+    /// the column contains none of the ids, can't use the GIN index
+    ContainsNone = 2,
+    /// the column has more than `length` ids, the ids are ignored
+    LengthGt = 3,
+    /// the column has less than `length` ids, the ids are ignored
+    LengthLt = 4,
+    /// the column has exactly `length` ids, the ids are ignored
+    LengthEq = 5,
+}
+impl ArrayOp {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ArrayOp::ContainsAll => "ARRAY_OP_CONTAINS_ALL",
+            ArrayOp::ContainsAny => "ARRAY_OP_CONTAINS_ANY",
+            ArrayOp::ContainsNone => "ARRAY_OP_CONTAINS_NONE",
+            ArrayOp::LengthGt => "ARRAY_OP_LENGTH_GT",
+            ArrayOp::LengthLt => "ARRAY_OP_LENGTH_LT",
+            ArrayOp::LengthEq => "ARRAY_OP_LENGTH_EQ",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ARRAY_OP_CONTAINS_ALL" => Some(Self::ContainsAll),
+            "ARRAY_OP_CONTAINS_ANY" => Some(Self::ContainsAny),
+            "ARRAY_OP_CONTAINS_NONE" => Some(Self::ContainsNone),
+            "ARRAY_OP_LENGTH_GT" => Some(Self::LengthGt),
+            "ARRAY_OP_LENGTH_LT" => Some(Self::LengthLt),
+            "ARRAY_OP_LENGTH_EQ" => Some(Self::LengthEq),
+            _ => None,
+        }
+    }
+}
+/// channel a user has been notified through
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum NotificationChannel {
+    Unspecified = 0,
+    Email = 1,
+    Sms = 2,
+    InApp = 3,
+}
+impl NotificationChannel {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            NotificationChannel::Unspecified => "NOTIFICATION_CHANNEL_UNSPECIFIED",
+            NotificationChannel::Email => "NOTIFICATION_CHANNEL_EMAIL",
+            NotificationChannel::Sms => "NOTIFICATION_CHANNEL_SMS",
+            NotificationChannel::InApp => "NOTIFICATION_CHANNEL_IN_APP",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "NOTIFICATION_CHANNEL_UNSPECIFIED" => Some(Self::Unspecified),
+            "NOTIFICATION_CHANNEL_EMAIL" => Some(Self::Email),
+            "NOTIFICATION_CHANNEL_SMS" => Some(Self::Sms),
+            "NOTIFICATION_CHANNEL_IN_APP" => Some(Self::InApp),
+            _ => None,
+        }
+    }
+}
+/// how the users of an aggregate are grouped into buckets
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Histogram {
+    Unspecified = 0,
+    Gender = 1,
+    /// by the day the user was created in UTC, keyed as 2024-05-01
+    CreatedDay = 2,
+    /// by the monday of the week the user was created in UTC, keyed as 2024-04-29
+    CreatedWeek = 3,
+    /// by the number of finished contents
+    FinishedLength = 4,
+}
+impl Histogram {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Histogram::Unspecified => "HISTOGRAM_UNSPECIFIED",
+            Histogram::Gender => "HISTOGRAM_GENDER",
+            Histogram::CreatedDay => "HISTOGRAM_CREATED_DAY",
+            Histogram::CreatedWeek => "HISTOGRAM_CREATED_WEEK",
+            Histogram::FinishedLength => "HISTOGRAM_FINISHED_LENGTH",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "HISTOGRAM_UNSPECIFIED" => Some(Self::Unspecified),
+            "HISTOGRAM_GENDER" => Some(Self::Gender),
+            "HISTOGRAM_CREATED_DAY" => Some(Self::CreatedDay),
+            "HISTOGRAM_CREATED_WEEK" => Some(Self::CreatedWeek),
+            "HISTOGRAM_FINISHED_LENGTH" => Some(Self::FinishedLength),
+            _ => None,
+        }
+    }
+}
+/// file formats users are imported from and exported to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum DataFormat {
+    Csv = 0,
+    /// one json object per line
+    Ndjson = 1,
+    /// export only
+    Parquet = 2,
+}
+impl DataFormat {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            DataFormat::Csv => "DATA_FORMAT_CSV",
+            DataFormat::Ndjson => "DATA_FORMAT_NDJSON",
+            DataFormat::Parquet => "DATA_FORMAT_PARQUET",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "DATA_FORMAT_CSV" => Some(Self::Csv),
+            "DATA_FORMAT_NDJSON" => Some(Self::Ndjson),
+            "DATA_FORMAT_PARQUET" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
 }
 /// Generated client implementations.
 pub mod user_stats_client {
@@ -155,6 +686,81 @@ pub mod user_stats_client {
                 .insert(GrpcMethod::new("user_stats.UserStats", "Query"));
             self.inner.server_streaming(req, path, codec).await
         }
+        /// the projected fields of the users matching the query
+        pub async fn query_profiles(
+            &mut self,
+            request: impl tonic::IntoRequest<super::QueryRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::UserProfile>>,
+            tonic::Status,
+        > {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/QueryProfiles");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "QueryProfiles"));
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// a single page of the query, page_size defaults to 100 and is capped at 1000
+        pub async fn query_page(
+            &mut self,
+            request: impl tonic::IntoRequest<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::QueryPageResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/QueryPage");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "QueryPage"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// number of users matching the query, pagination is ignored
+        pub async fn count(
+            &mut self,
+            request: impl tonic::IntoRequest<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::CountResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/Count");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "Count"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// histograms of the users matching the query
+        pub async fn aggregate(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AggregateRequest>,
+        ) -> std::result::Result<tonic::Response<super::AggregateResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/Aggregate");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "Aggregate"));
+            self.inner.unary(req, path, codec).await
+        }
         pub async fn raw_query(
             &mut self,
             request: impl tonic::IntoRequest<super::RawQueryRequest>,
@@ -173,6 +779,85 @@ pub mod user_stats_client {
                 .insert(GrpcMethod::new("user_stats.UserStats", "RawQuery"));
             self.inner.server_streaming(req, path, codec).await
         }
+        /// apply user events to user_stats, in batches of one transaction each. A
+        /// batch with an invalid event is rejected and ends the stream, batches
+        /// before it stay applied
+        pub async fn ingest(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::UserEvent>,
+        ) -> std::result::Result<tonic::Response<super::IngestResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/Ingest");
+            let mut req = request.into_streaming_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "Ingest"));
+            self.inner.client_streaming(req, path, codec).await
+        }
+        /// insert or update users from a file, in a single transaction. Columns
+        /// missing from a row keep the current value of existing users
+        pub async fn import(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::ImportRequest>,
+        ) -> std::result::Result<tonic::Response<super::ImportResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/Import");
+            let mut req = request.into_streaming_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "Import"));
+            self.inner.client_streaming(req, path, codec).await
+        }
+        /// the users matching the query as a file
+        pub async fn export(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExportRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::ExportChunk>>,
+            tonic::Status,
+        > {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/Export");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "Export"));
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// update the last notification time of the channel for the given users
+        pub async fn mark_notified(
+            &mut self,
+            request: impl tonic::IntoRequest<super::MarkNotifiedRequest>,
+        ) -> std::result::Result<tonic::Response<super::MarkNotifiedResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/MarkNotified");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "MarkNotified"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -191,6 +876,31 @@ pub mod user_stats_server {
             &self,
             request: tonic::Request<super::QueryRequest>,
         ) -> std::result::Result<tonic::Response<Self::QueryStream>, tonic::Status>;
+        /// Server streaming response type for the QueryProfiles method.
+        type QueryProfilesStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::UserProfile, tonic::Status>,
+            > + Send
+            + 'static;
+        /// the projected fields of the users matching the query
+        async fn query_profiles(
+            &self,
+            request: tonic::Request<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<Self::QueryProfilesStream>, tonic::Status>;
+        /// a single page of the query, page_size defaults to 100 and is capped at 1000
+        async fn query_page(
+            &self,
+            request: tonic::Request<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::QueryPageResponse>, tonic::Status>;
+        /// number of users matching the query, pagination is ignored
+        async fn count(
+            &self,
+            request: tonic::Request<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::CountResponse>, tonic::Status>;
+        /// histograms of the users matching the query
+        async fn aggregate(
+            &self,
+            request: tonic::Request<super::AggregateRequest>,
+        ) -> std::result::Result<tonic::Response<super::AggregateResponse>, tonic::Status>;
         /// Server streaming response type for the RawQuery method.
         type RawQueryStream: tonic::codegen::tokio_stream::Stream<
                 Item = std::result::Result<super::User, tonic::Status>,
@@ -200,6 +910,34 @@ pub mod user_stats_server {
             &self,
             request: tonic::Request<super::RawQueryRequest>,
         ) -> std::result::Result<tonic::Response<Self::RawQueryStream>, tonic::Status>;
+        /// apply user events to user_stats, in batches of one transaction each. A
+        /// batch with an invalid event is rejected and ends the stream, batches
+        /// before it stay applied
+        async fn ingest(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::UserEvent>>,
+        ) -> std::result::Result<tonic::Response<super::IngestResponse>, tonic::Status>;
+        /// insert or update users from a file, in a single transaction. Columns
+        /// missing from a row keep the current value of existing users
+        async fn import(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::ImportRequest>>,
+        ) -> std::result::Result<tonic::Response<super::ImportResponse>, tonic::Status>;
+        /// Server streaming response type for the Export method.
+        type ExportStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::ExportChunk, tonic::Status>,
+            > + Send
+            + 'static;
+        /// the users matching the query as a file
+        async fn export(
+            &self,
+            request: tonic::Request<super::ExportRequest>,
+        ) -> std::result::Result<tonic::Response<Self::ExportStream>, tonic::Status>;
+        /// update the last notification time of the channel for the given users
+        async fn mark_notified(
+            &self,
+            request: tonic::Request<super::MarkNotifiedRequest>,
+        ) -> std::result::Result<tonic::Response<super::MarkNotifiedResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct UserStatsServer<T: UserStats> {
@@ -317,6 +1055,166 @@ pub mod user_stats_server {
                     };
                     Box::pin(fut)
                 }
+                "/user_stats.UserStats/QueryProfiles" => {
+                    #[allow(non_camel_case_types)]
+                    struct QueryProfilesSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::ServerStreamingService<super::QueryRequest>
+                        for QueryProfilesSvc<T>
+                    {
+                        type Response = super::UserProfile;
+                        type ResponseStream = T::QueryProfilesStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::QueryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserStats>::query_profiles(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = QueryProfilesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/QueryPage" => {
+                    #[allow(non_camel_case_types)]
+                    struct QueryPageSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::QueryRequest> for QueryPageSvc<T> {
+                        type Response = super::QueryPageResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::QueryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::query_page(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = QueryPageSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/Count" => {
+                    #[allow(non_camel_case_types)]
+                    struct CountSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::QueryRequest> for CountSvc<T> {
+                        type Response = super::CountResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::QueryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { <T as UserStats>::count(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CountSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/Aggregate" => {
+                    #[allow(non_camel_case_types)]
+                    struct AggregateSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::AggregateRequest> for AggregateSvc<T> {
+                        type Response = super::AggregateResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AggregateRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::aggregate(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = AggregateSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/user_stats.UserStats/RawQuery" => {
                     #[allow(non_camel_case_types)]
                     struct RawQuerySvc<T: UserStats>(pub Arc<T>);
@@ -360,6 +1258,165 @@ pub mod user_stats_server {
                     };
                     Box::pin(fut)
                 }
+                "/user_stats.UserStats/Ingest" => {
+                    #[allow(non_camel_case_types)]
+                    struct IngestSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::ClientStreamingService<super::UserEvent> for IngestSvc<T> {
+                        type Response = super::IngestResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<tonic::Streaming<super::UserEvent>>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::ingest(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = IngestSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.client_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/Import" => {
+                    #[allow(non_camel_case_types)]
+                    struct ImportSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::ClientStreamingService<super::ImportRequest> for ImportSvc<T> {
+                        type Response = super::ImportResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<tonic::Streaming<super::ImportRequest>>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::import(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ImportSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.client_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/Export" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExportSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::ServerStreamingService<super::ExportRequest> for ExportSvc<T> {
+                        type Response = super::ExportChunk;
+                        type ResponseStream = T::ExportStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExportRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::export(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ExportSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/MarkNotified" => {
+                    #[allow(non_camel_case_types)]
+                    struct MarkNotifiedSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::MarkNotifiedRequest> for MarkNotifiedSvc<T> {
+                        type Response = super::MarkNotifiedResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::MarkNotifiedRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserStats>::mark_notified(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = MarkNotifiedSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)